@@ -0,0 +1,45 @@
+use ws;
+
+use enums::errcode::*;
+use enums::privtype::*;
+
+use Server;
+
+macro_rules! require {
+    ($self_: expr, $e:expr, $err:expr) => (match $e {
+        Some(x) => x,
+        None => { $self_.send_error($err); return Ok(()); }
+    })
+}
+
+impl Server {
+    // handles both archiveroom and unarchiveroom
+    pub fn archive_room(&mut self, archived: bool, roomid: Option<i32>)
+            -> ws::Result<()> {
+        let roomid = require!(self, roomid.or(self.roomid), ErrCode::NoRoomId);
+        if !self.is_joined(roomid) {
+            self.send_error(ErrCode::NotJoined);
+            return Ok(());
+        }
+        require!(self, self.userid, ErrCode::NeedLogin);
+
+        if !self.has_privilege(roomid, PrivType::ModifyRoom) {
+            self.send_error(ErrCode::NoPrivilege);
+            return Ok(());
+        }
+
+        let mut room = require!(self, self.glavra.store.get_room(roomid),
+            ErrCode::RoomNotExist);
+        if room.archived == archived {
+            return Ok(());
+        }
+
+        self.glavra.store.set_archived(roomid, archived);
+        room.archived = archived;
+
+        self.glavra.rooms.broadcast(roomid,
+            self.roominfo_frame(room).to_json());
+
+        Ok(())
+    }
+}
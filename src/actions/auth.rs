@@ -1,81 +1,68 @@
-use util::*;
+use password;
 
 use ws;
 
-use serde_json;
-use serde_json::{Value, Map};
-
-use enums::errcode::ErrCode;
+use enums::errcode::*;
+use protocol::Response;
 
 use Server;
 
-macro_rules! require {
-    ($self_: expr, $e:expr, $err:expr) => (match $e {
-        Some(x) => x,
-        None => { $self_.send_error($err); return Ok(()); }
-    })
-}
+impl Server {
 
-macro_rules! rrequire {
-    ($self_: expr, $e:expr, $err:expr) => (match $e {
-        Ok(x) => x,
-        Err(_) => { $self_.send_error($err); return Ok(()); }
-    })
-}
+    pub fn auth(&mut self, username: String, password: String,
+                label: Option<String>) -> ws::Result<()> {
+        if let Some(wait) = self.login_wait(Some(&username[..])) {
+            self.send_retry(ErrCode::LoginThrottled, wait);
+            return Ok(());
+        }
 
-impl Server {
+        if password.len() > password::MAX_LEN {
+            self.send_error(ErrCode::PasswordTooLong);
+            return Ok(());
+        }
 
-    pub fn auth(&mut self, json: Map<String, Value>) -> ws::Result<()> {
-        let (username, password, mut userid) =
-            (require!(self, get_string(&json, "username"), ErrCode::Malformed),
-             require!(self, get_string(&json, "password"), ErrCode::Malformed),
-             -1);
+        let mut userid = -1;
+        let config = &self.glavra.config;
 
-        let lock = self.glavra.lock().unwrap();
-        let auth_success = {
-            let auth_query = lock.conn.query("
-                SELECT id, salt, hash
-                FROM users
-                WHERE username = $1", &[&username]).unwrap();
-            if auth_query.is_empty() {
-                // the username doesn't exist
+        let auth_success = match self.glavra.store.find_user(&username) {
+            None => {
+                // the username doesn't exist, but hash anyway so that this
+                // takes as long as a wrong password would
+                password::hash(&password, config);
                 false
-            } else {
-                let row = auth_query.get(0);
-                userid = row.get(0);
-                let salt: Vec<u8> = row.get(1);
-                let salt = salt.as_slice();
-                // I can't believe I actually have to do this
-                let salt = [salt[0],  salt[1],  salt[2],  salt[3],
-                            salt[4],  salt[5],  salt[6],  salt[7],
-                            salt[8],  salt[9],  salt[10], salt[11],
-                            salt[12], salt[13], salt[14], salt[15]];
-                let hash: Vec<u8> = row.get(2);
-                hash == hash_pwd(salt, &password)
+            },
+            Some(user) => {
+                userid = user.id;
+                let success = password::verify(&password, &user.password);
+                // if this fails, the old hash still works for next time
+                if success && password::needs_rehash(&user.password, config) {
+                    if let Some(hash) = password::hash(&password, config) {
+                        self.glavra.store.set_password(userid, &hash);
+                    }
+                }
+                success
             }
         };
 
-        let mut builder = json!({
-            "type": "auth",
-            "success": auth_success
-        });
-        if auth_success {
-            let mut obj = builder.as_object_mut().unwrap();
-            obj.insert("token".to_string(),
-                json!(self.get_auth_token(userid, &lock)));
-            obj.insert("userid".to_string(),
-                json!(userid));
-        }
-        try!(self.out.send(serde_json::to_string(&builder).unwrap()));
-
-        if auth_success {
-            self.userid = Some(userid);
-            if self.roomid.is_some() {
-                self.system_message(format!("{} has connected", username), &lock);
-            }
+        let session = if auth_success {
+            self.glavra.failed_users.clear(&username);
+            Some(self.start_session(userid, label))
+        } else {
+            self.login_failed(Some(&username[..]));
+            None
+        };
+        // logged in before the client hears about it, so that anything sent
+        // to the user from then on reaches this connection too
+        if let Some((id, _)) = session {
+            self.log_in(userid, id);
         }
 
-        Ok(())
+        self.send(Response::Auth {
+            success: auth_success,
+            token: session.map(|(_, token)| token),
+            userid: if auth_success { Some(userid) } else { None },
+            username: None
+        })
     }
 
 }
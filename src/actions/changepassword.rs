@@ -0,0 +1,49 @@
+use password;
+
+use ws;
+
+use enums::errcode::*;
+use protocol::Response;
+
+use Server;
+
+macro_rules! require {
+    ($self_: expr, $e:expr, $err:expr) => (match $e {
+        Some(x) => x,
+        None => { $self_.send_error($err); return Ok(()); }
+    })
+}
+
+impl Server {
+    pub fn change_password(&mut self, password: String, newpassword: String)
+            -> ws::Result<()> {
+        let userid = require!(self, self.userid, ErrCode::NeedLogin);
+        let user = require!(self, self.glavra.store.get_user(userid),
+            ErrCode::UserNotExist);
+
+        if let Some(wait) = self.login_wait(Some(&user.username[..])) {
+            self.send_retry(ErrCode::LoginThrottled, wait);
+            return Ok(());
+        }
+        if password.len() > password::MAX_LEN ||
+                newpassword.len() > password::MAX_LEN {
+            self.send_error(ErrCode::PasswordTooLong);
+            return Ok(());
+        }
+
+        if !password::verify(&password, &user.password) {
+            self.login_failed(Some(&user.username[..]));
+            return self.send(Response::ChangePassword { success: false });
+        }
+
+        let hash = require!(self,
+            password::hash(&newpassword, &self.glavra.config),
+            ErrCode::HashFailed);
+        self.glavra.store.set_password(userid, &hash);
+        self.send(Response::ChangePassword { success: true })?;
+
+        let ids = self.glavra.store.revoke_sessions(userid, self.session);
+        self.end_sessions(&ids);
+        Ok(())
+    }
+}
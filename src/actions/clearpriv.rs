@@ -0,0 +1,57 @@
+use ws;
+
+use enums::errcode::*;
+use enums::privtype::*;
+
+use protocol::*;
+
+use Server;
+
+macro_rules! require {
+    ($self_: expr, $e:expr, $err:expr) => (match $e {
+        Some(x) => x,
+        None => { $self_.send_error($err); return Ok(()); }
+    })
+}
+
+impl Server {
+    pub fn clear_priv(&mut self, privtype: String, userid: Option<i32>,
+                      roomid: Option<i32>) -> ws::Result<()> {
+        let roomid = require!(self, roomid.or(self.roomid), ErrCode::NoRoomId);
+        if !self.is_joined(roomid) {
+            self.send_error(ErrCode::NotJoined);
+            return Ok(());
+        }
+        require!(self, self.userid, ErrCode::NeedLogin);
+
+        if !self.has_privilege(roomid, PrivType::ModifyPrivs) {
+            self.send_error(ErrCode::NoPrivilege);
+            return Ok(());
+        }
+
+        let privtype = require!(self, PrivType::from_name(&privtype),
+            ErrCode::Malformed);
+        if let Some(userid) = userid {
+            if !self.can_modify(roomid, userid) {
+                self.send_error(ErrCode::NoPrivilege);
+                return Ok(());
+            }
+        }
+
+        // clearing a row that isn't there is fine, there's just nothing to
+        // tell anyone about
+        if self.glavra.store.clear_privilege(roomid, userid, privtype) {
+            self.glavra.rooms.broadcast(roomid, Response::ClearPriv {
+                roomid,
+                privtype: privtype.name().to_string(),
+                userid
+            }.to_json());
+            // what's left may well be less than the row gave them
+            if privtype == PrivType::ReadAccess {
+                self.recheck_access(roomid, userid);
+            }
+        }
+
+        Ok(())
+    }
+}
@@ -0,0 +1,40 @@
+use ws;
+
+use enums::errcode::*;
+use enums::privtype::*;
+
+use protocol::*;
+use util;
+
+use Server;
+
+macro_rules! require {
+    ($self_: expr, $e:expr, $err:expr) => (match $e {
+        Some(x) => x,
+        None => { $self_.send_error($err); return Ok(()); }
+    })
+}
+
+impl Server {
+    pub fn create_invite(&mut self, roomid: Option<i32>) -> ws::Result<()> {
+        let roomid = require!(self, roomid.or(self.roomid), ErrCode::NoRoomId);
+        if !self.is_joined(roomid) {
+            self.send_error(ErrCode::NotJoined);
+            return Ok(());
+        }
+        let userid = require!(self, self.userid, ErrCode::NeedLogin);
+
+        if !self.has_privilege(roomid, PrivType::ModifyRoom) ||
+                self.is_direct(roomid) {
+            self.send_error(ErrCode::NoPrivilege);
+            return Ok(());
+        }
+
+        let token = util::random_token();
+        self.glavra.store.create_invite(roomid, userid, &token);
+
+        self.send(Response::Invite { roomid, token })?;
+
+        Ok(())
+    }
+}
@@ -1,9 +1,5 @@
-use util::*;
-
 use ws;
 
-use serde_json::{Value, Map};
-
 use time;
 
 use enums::errcode::*;
@@ -20,50 +16,42 @@ macro_rules! require {
     })
 }
 
-macro_rules! rrequire {
-    ($self_: expr, $e:expr, $err:expr) => (match $e {
-        Ok(x) => x,
-        Err(_) => { $self_.send_error($err); return Ok(()); }
-    })
-}
-
 impl Server {
-    pub fn delete(&mut self, json: Map<String, Value>) -> ws::Result<()> {
-        let lock = self.glavra.lock().unwrap();
-        let id = require!(self, get_i32(&json, "id"), ErrCode::Malformed);
-        let roomid = require!(self, self.roomid, ErrCode::NoRoomId);
-        let userid = require!(self, self.userid.clone(), ErrCode::NeedLogin);
-        let muserid = rrequire!(self, self.get_sender(id, &lock), ErrCode::Malformed);
-        let own = userid == muserid;
+    pub fn delete(&mut self, id: i32) -> ws::Result<()> {
+        let userid = require!(self, self.userid, ErrCode::NeedLogin);
+        let old = require!(self, self.glavra.store.get_message(id),
+            ErrCode::Malformed);
+        let roomid = old.roomid;
+        if !self.is_joined(roomid) {
+            self.send_error(ErrCode::NotJoined);
+            return Ok(());
+        }
+
+        if self.is_archived(roomid) {
+            self.send_error(ErrCode::RoomArchived);
+            return Ok(());
+        }
+
+        let own = userid == old.userid;
 
-        let (threshold, period) = self.get_privilege(roomid, &self.userid,
-            if own { PrivType::DeleteOwn } else { PrivType::DeleteOthers },
-            &lock).unwrap();
+        let (threshold, since) = self.get_privilege(roomid, &self.userid,
+            if own { PrivType::DeleteOwn } else { PrivType::DeleteOthers });
 
-        if lock.conn.query("
-                    SELECT COUNT(DISTINCT h.messageid) >= $1
-                    FROM history h
-                    INNER JOIN messages m ON m.id = h.messageid
-                    WHERE m.roomid = $3
-                      AND m.userid = $4
-                      AND m.text = ''
-                      AND h.tstamp BETWEEN now() - (interval '1s') * $2
-                                   AND now()",
-                &[&threshold, &period, &roomid, &userid])
-                    .unwrap().get(0).get(0) {
+        if self.glavra.store.count_deletes(roomid, userid, since)
+                >= threshold {
             self.send_error(ErrCode::RateLimit);
             return Ok(());
         }
 
         let message = Message {
-            id: require!(self, get_i32(&json, "id"), ErrCode::Malformed),
-            roomid: roomid,
-            userid: require!(self, self.userid.clone(), ErrCode::NeedLogin),
+            id,
+            roomid,
+            userid,
             replyid: None,
             text: String::new(),
             timestamp: time::get_time()
         };
-        self.send_message(message, &lock);
+        self.send_message(message);
         Ok(())
     }
 }
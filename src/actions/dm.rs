@@ -0,0 +1,46 @@
+use ws;
+
+use enums::errcode::*;
+
+use protocol::*;
+
+use Server;
+
+macro_rules! require {
+    ($self_: expr, $e:expr, $err:expr) => (match $e {
+        Some(x) => x,
+        None => { $self_.send_error($err); return Ok(()); }
+    })
+}
+
+// including the user who opens the conversation
+const MAX_PARTICIPANTS: usize = 10;
+
+impl Server {
+    pub fn dm(&mut self, userids: Vec<i32>) -> ws::Result<()> {
+        let me = require!(self, self.userid, ErrCode::NeedLogin);
+
+        let mut userids = userids;
+        userids.push(me);
+        userids.sort();
+        userids.dedup();
+        if userids.len() < 2 || userids.len() > MAX_PARTICIPANTS {
+            self.send_error(ErrCode::Malformed);
+            return Ok(());
+        }
+        for &userid in &userids {
+            require!(self, self.glavra.store.get_user(userid),
+                ErrCode::UserNotExist);
+        }
+
+        // there's only ever one conversation per set of participants
+        let roomid = match self.glavra.store.find_direct(&userids) {
+            Some(roomid) => roomid,
+            None => self.glavra.create_direct(&userids)
+        };
+
+        self.send(Response::Dm(self.dm_frame(roomid)))?;
+
+        Ok(())
+    }
+}
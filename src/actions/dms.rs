@@ -0,0 +1,26 @@
+use ws;
+
+use enums::errcode::*;
+
+use protocol::*;
+
+use Server;
+
+macro_rules! require {
+    ($self_: expr, $e:expr, $err:expr) => (match $e {
+        Some(x) => x,
+        None => { $self_.send_error($err); return Ok(()); }
+    })
+}
+
+impl Server {
+    pub fn dms(&mut self) -> ws::Result<()> {
+        let userid = require!(self, self.userid, ErrCode::NeedLogin);
+
+        let dms = self.glavra.store.list_direct(userid).into_iter()
+            .map(|roomid| self.dm_frame(roomid)).collect();
+        self.send(Response::DmList { dms })?;
+
+        Ok(())
+    }
+}
@@ -1,9 +1,5 @@
-use util::*;
-
 use ws;
 
-use serde_json::{Value, Map};
-
 use time;
 
 use enums::errcode::*;
@@ -20,56 +16,52 @@ macro_rules! require {
     })
 }
 
-macro_rules! rrequire {
-    ($self_: expr, $e:expr, $err:expr) => (match $e {
-        Ok(x) => x,
-        Err(_) => { $self_.send_error($err); return Ok(()); }
-    })
-}
-
 impl Server {
-    pub fn edit(&mut self, json: Map<String, Value>) -> ws::Result<()> {
-        let text = require!(self, get_string(&json, "text"),
-            ErrCode::Malformed);
+    pub fn edit(&mut self, id: i32, text: String, replyid: Option<i32>)
+            -> ws::Result<()> {
         if text.is_empty() {
             self.send_error(ErrCode::EmptyMsg);
         } else {
-            let lock = self.glavra.lock().unwrap();
-            let id = require!(self, get_i32(&json, "id"), ErrCode::Malformed);
-            let roomid = require!(self, self.roomid, ErrCode::NoRoomId);
-            let userid = require!(self, self.userid.clone(), ErrCode::NeedLogin);
-            let muserid = rrequire!(self, self.get_sender(id, &lock), ErrCode::Malformed);
-            let own = userid == muserid;
+            let userid = require!(self, self.userid, ErrCode::NeedLogin);
+            let old = require!(self, self.glavra.store.get_message(id),
+                ErrCode::Malformed);
+            // edits happen in whatever room the message is in
+            let roomid = old.roomid;
+            if !self.is_joined(roomid) {
+                self.send_error(ErrCode::NotJoined);
+                return Ok(());
+            }
+
+            if self.is_archived(roomid) {
+                self.send_error(ErrCode::RoomArchived);
+                return Ok(());
+            }
+            if !self.valid_reply(roomid, replyid, Some(id)) {
+                self.send_error(ErrCode::InvalidReply);
+                return Ok(());
+            }
+
+            let own = userid == old.userid;
 
-            let (threshold, period) = self.get_privilege(roomid,
+            let (threshold, since) = self.get_privilege(roomid,
                 &self.userid,
-                if own { PrivType::EditOwn } else { PrivType::EditOthers },
-                &lock).unwrap();
+                if own { PrivType::EditOwn } else { PrivType::EditOthers });
 
-            if lock.conn.query("
-                        SELECT COUNT(*) >= $1
-                        FROM history h
-                        INNER JOIN messages m ON m.id = h.messageid
-                        WHERE m.roomid = $3
-                          AND m.userid = $4
-                          AND m.text != ''
-                          AND h.tstamp BETWEEN now() - (interval '1s') * $2
-                                       AND now()",
-                    &[&threshold, &period, &roomid, &userid])
-                        .unwrap().get(0).get(0) {
+            if self.glavra.store.count_edits(roomid, userid, since)
+                    >= threshold {
                 self.send_error(ErrCode::RateLimit);
                 return Ok(());
             }
 
             let message = Message {
-                id: id,
-                roomid: roomid,
-                userid: userid,
-                replyid: get_i32(&json, "replyid"),
-                text: text,
+                id,
+                roomid,
+                userid,
+                replyid,
+                text,
                 timestamp: time::get_time()
             };
-            self.send_message(message, &lock);
+            self.send_message(message);
         }
         Ok(())
     }
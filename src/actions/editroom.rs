@@ -0,0 +1,54 @@
+use ws;
+
+use enums::errcode::*;
+use enums::privtype::*;
+
+use Server;
+
+macro_rules! require {
+    ($self_: expr, $e:expr, $err:expr) => (match $e {
+        Some(x) => x,
+        None => { $self_.send_error($err); return Ok(()); }
+    })
+}
+
+impl Server {
+    pub fn edit_room(&mut self, name: Option<String>, desc: Option<String>,
+                     topic: Option<String>, roomid: Option<i32>)
+            -> ws::Result<()> {
+        let roomid = require!(self, roomid.or(self.roomid), ErrCode::NoRoomId);
+        if !self.is_joined(roomid) {
+            self.send_error(ErrCode::NotJoined);
+            return Ok(());
+        }
+        require!(self, self.userid, ErrCode::NeedLogin);
+
+        if !self.has_privilege(roomid, PrivType::ModifyRoom) {
+            self.send_error(ErrCode::NoPrivilege);
+            return Ok(());
+        }
+
+        let mut room = require!(self, self.glavra.store.get_room(roomid),
+            ErrCode::RoomNotExist);
+        if room.archived {
+            self.send_error(ErrCode::RoomArchived);
+            return Ok(());
+        }
+
+        room.name = name.unwrap_or(room.name);
+        room.description = desc.unwrap_or(room.description);
+        room.topic = topic.unwrap_or(room.topic);
+        if room.name.is_empty() {
+            self.send_error(ErrCode::Malformed);
+            return Ok(());
+        }
+
+        self.glavra.store.edit_room(roomid, &room.name, &room.description,
+            &room.topic);
+
+        self.glavra.rooms.broadcast(roomid,
+            self.roominfo_frame(room).to_json());
+
+        Ok(())
+    }
+}
@@ -0,0 +1,40 @@
+use ws;
+
+use enums::errcode::*;
+use enums::privtype::*;
+
+use protocol::*;
+
+use Server;
+
+macro_rules! require {
+    ($self_: expr, $e:expr, $err:expr) => (match $e {
+        Some(x) => x,
+        None => { $self_.send_error($err); return Ok(()); }
+    })
+}
+
+impl Server {
+    pub fn get_privs(&mut self, roomid: Option<i32>) -> ws::Result<()> {
+        let roomid = require!(self, roomid.or(self.roomid), ErrCode::NoRoomId);
+        if !self.is_joined(roomid) {
+            self.send_error(ErrCode::NotJoined);
+            return Ok(());
+        }
+        require!(self, self.userid, ErrCode::NeedLogin);
+
+        if !self.has_privilege(roomid, PrivType::ModifyPrivs) {
+            self.send_error(ErrCode::NoPrivilege);
+            return Ok(());
+        }
+
+        let privileges = self.glavra.store.list_privileges(roomid).iter()
+            .map(|privilege| self.privilege_frame(privilege)).collect();
+        self.send(Response::Privileges {
+            roomid,
+            privileges
+        })?;
+
+        Ok(())
+    }
+}
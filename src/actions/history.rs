@@ -1,10 +1,7 @@
-use util::*;
-
 use ws;
 
-use serde_json::{Value, Map};
-
-use enums::errcode::ErrCode;
+use enums::errcode::*;
+use enums::privtype::*;
 
 use Server;
 
@@ -15,18 +12,24 @@ macro_rules! require {
     })
 }
 
-macro_rules! rrequire {
-    ($self_: expr, $e:expr, $err:expr) => (match $e {
-        Ok(x) => x,
-        Err(_) => { $self_.send_error($err); return Ok(()); }
-    })
-}
-
 impl Server {
-    pub fn history(&mut self, json: Map<String, Value>) -> ws::Result<()> {
-        let id = require!(self, get_i32(&json, "id"), ErrCode::Malformed);
-        let lock = self.glavra.lock().unwrap();
-        try!(self.out.send(self.history_json(id, &lock)));
+    pub fn history(&mut self, id: i32) -> ws::Result<()> {
+        let message = require!(self, self.glavra.store.get_message(id),
+            ErrCode::MessageNotExist);
+        let room = require!(self, self.glavra.store.get_room(message.roomid),
+            ErrCode::RoomNotExist);
+        // ReadAccess is usually everyone's, so without this anyone could read
+        // an invite-only room one message id at a time
+        if !self.can_join(&room) {
+            self.send_error(ErrCode::NotMember);
+            return Ok(());
+        }
+        if !self.has_privilege(message.roomid, PrivType::ReadAccess) {
+            self.send_error(ErrCode::NoPrivilege);
+            return Ok(());
+        }
+
+        self.send(self.history_frame(id, message.roomid))?;
         Ok(())
     }
 }
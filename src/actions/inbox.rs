@@ -0,0 +1,36 @@
+use ws;
+
+use enums::errcode::*;
+
+use protocol::*;
+
+use Server;
+
+macro_rules! require {
+    ($self_: expr, $e:expr, $err:expr) => (match $e {
+        Some(x) => x,
+        None => { $self_.send_error($err); return Ok(()); }
+    })
+}
+
+impl Server {
+    pub fn inbox(&mut self) -> ws::Result<()> {
+        let userid = require!(self, self.userid, ErrCode::NeedLogin);
+
+        // the message may have been moved somewhere the user can't read
+        // since, in which case it's left out
+        let store = &self.glavra.store;
+        let notifications = store.unread_notifications(userid,
+                self.glavra.config.history_size).into_iter()
+            .filter_map(|notification| store.get_message(notification.messageid)
+                .map(|message| (notification, message)))
+            .filter(|(_, message)| store.get_room(message.roomid)
+                .is_some_and(|room| self.user_can_read(&room, userid)))
+            .map(|(notification, message)|
+                self.notification_frame(&notification, &message))
+            .collect();
+        self.send(Response::Inbox { notifications })?;
+
+        Ok(())
+    }
+}
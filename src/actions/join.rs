@@ -0,0 +1,21 @@
+use ws;
+
+use Server;
+
+impl Server {
+    pub fn join(&mut self, roomid: i32, invite: Option<String>)
+            -> ws::Result<()> {
+        // nothing to catch up on
+        if self.is_joined(roomid) {
+            return Ok(());
+        }
+
+        match self.check_join(roomid, invite) {
+            Ok(room) => self.join_room(room),
+            Err(code) => {
+                self.send_error(code);
+                Ok(())
+            }
+        }
+    }
+}
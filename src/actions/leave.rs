@@ -0,0 +1,21 @@
+use ws;
+
+use enums::errcode::*;
+
+use protocol::*;
+
+use Server;
+
+impl Server {
+    pub fn leave(&mut self, roomid: i32) -> ws::Result<()> {
+        if !self.is_joined(roomid) {
+            self.send_error(ErrCode::NotJoined);
+            return Ok(());
+        }
+
+        self.leave_room(roomid);
+        self.send(Response::Leave { roomid })?;
+
+        Ok(())
+    }
+}
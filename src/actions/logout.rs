@@ -0,0 +1,27 @@
+use ws;
+
+use enums::errcode::*;
+
+use Server;
+
+macro_rules! require {
+    ($self_: expr, $e:expr, $err:expr) => (match $e {
+        Some(x) => x,
+        None => { $self_.send_error($err); return Ok(()); }
+    })
+}
+
+impl Server {
+    pub fn logout(&mut self, id: Option<i32>) -> ws::Result<()> {
+        let userid = require!(self, self.userid, ErrCode::NeedLogin);
+        let id = require!(self, id.or(self.session), ErrCode::NeedLogin);
+
+        if !self.glavra.store.revoke_session(userid, id) {
+            self.send_error(ErrCode::SessionNotExist);
+            return Ok(());
+        }
+
+        self.end_sessions(&[id]);
+        Ok(())
+    }
+}
@@ -0,0 +1,22 @@
+use ws;
+
+use enums::errcode::*;
+
+use Server;
+
+macro_rules! require {
+    ($self_: expr, $e:expr, $err:expr) => (match $e {
+        Some(x) => x,
+        None => { $self_.send_error($err); return Ok(()); }
+    })
+}
+
+impl Server {
+    pub fn logout_all(&mut self) -> ws::Result<()> {
+        let userid = require!(self, self.userid, ErrCode::NeedLogin);
+
+        let ids = self.glavra.store.revoke_sessions(userid, None);
+        self.end_sessions(&ids);
+        Ok(())
+    }
+}
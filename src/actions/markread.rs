@@ -0,0 +1,52 @@
+use ws;
+
+use enums::errcode::*;
+
+use protocol::*;
+
+use Server;
+
+macro_rules! require {
+    ($self_: expr, $e:expr, $err:expr) => (match $e {
+        Some(x) => x,
+        None => { $self_.send_error($err); return Ok(()); }
+    })
+}
+
+impl Server {
+    pub fn mark_read(&mut self, messageid: Option<i32>, roomid: Option<i32>)
+            -> ws::Result<()> {
+        let roomid = require!(self, roomid.or(self.roomid), ErrCode::NoRoomId);
+        if !self.is_joined(roomid) {
+            self.send_error(ErrCode::NotJoined);
+            return Ok(());
+        }
+        let userid = require!(self, self.userid, ErrCode::NeedLogin);
+
+        let messageid = match messageid {
+            Some(messageid) => {
+                require!(self, self.glavra.store.get_message(messageid)
+                    .filter(|m| m.roomid == roomid), ErrCode::MessageNotExist);
+                messageid
+            },
+            None => match self.glavra.store.recent_messages(roomid, 1).pop() {
+                Some(message) => message.id,
+                // nothing to have read
+                None => return Ok(())
+            }
+        };
+
+        self.glavra.store.set_read_marker(userid, roomid, messageid);
+
+        // the marker only ever moves forwards, so this may not be the one
+        // that was asked for
+        let messageid = self.glavra.store.get_read_marker(userid, roomid)
+            .unwrap_or(messageid);
+        self.glavra.users.broadcast(userid, Response::ReadMarker {
+            roomid,
+            messageid
+        }.to_json());
+
+        Ok(())
+    }
+}
@@ -0,0 +1,44 @@
+use ws;
+
+use enums::errcode::*;
+use enums::privtype::*;
+
+use protocol::*;
+
+use Server;
+
+macro_rules! require {
+    ($self_: expr, $e:expr, $err:expr) => (match $e {
+        Some(x) => x,
+        None => { $self_.send_error($err); return Ok(()); }
+    })
+}
+
+impl Server {
+    pub fn members(&mut self, roomid: Option<i32>) -> ws::Result<()> {
+        let roomid = require!(self, roomid.or(self.roomid), ErrCode::NoRoomId);
+        if !self.is_joined(roomid) {
+            self.send_error(ErrCode::NotJoined);
+            return Ok(());
+        }
+
+        if !self.has_privilege(roomid, PrivType::ReadAccess) {
+            self.send_error(ErrCode::NoPrivilege);
+            return Ok(());
+        }
+
+        let members = self.glavra.store.list_members(roomid).into_iter()
+            .filter_map(|(userid, role)| self.get_username(userid)
+                .map(|username| MemberFrame {
+                    userid,
+                    username,
+                    role: role.name().to_string()
+                })).collect();
+        self.send(Response::Members {
+            roomid,
+            members
+        })?;
+
+        Ok(())
+    }
+}
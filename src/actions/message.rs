@@ -1,9 +1,5 @@
-use util::*;
-
 use ws;
 
-use serde_json::{Value, Map};
-
 use time;
 
 use enums::errcode::*;
@@ -20,49 +16,47 @@ macro_rules! require {
     })
 }
 
-macro_rules! rrequire {
-    ($self_: expr, $e:expr, $err:expr) => (match $e {
-        Ok(x) => x,
-        Err(_) => { $self_.send_error($err); return Ok(()); }
-    })
-}
-
 impl Server {
-    pub fn message(&mut self, json: Map<String, Value>) -> ws::Result<()> {
-        let text = require!(self, get_string(&json, "text"), ErrCode::Malformed);
-
+    pub fn message(&mut self, text: String, replyid: Option<i32>,
+                   roomid: Option<i32>) -> ws::Result<()> {
         if text.is_empty() {
             self.send_error(ErrCode::EmptyMsg);
         } else {
-            let roomid = require!(self, self.roomid, ErrCode::NoRoomId);
-            let userid = require!(self, self.userid.clone(), ErrCode::NeedLogin);
+            let roomid = require!(self, roomid.or(self.roomid),
+                ErrCode::NoRoomId);
+            if !self.is_joined(roomid) {
+                self.send_error(ErrCode::NotJoined);
+                return Ok(());
+            }
+            let userid = require!(self, self.userid, ErrCode::NeedLogin);
+
+            if self.is_archived(roomid) {
+                self.send_error(ErrCode::RoomArchived);
+                return Ok(());
+            }
+            if !self.valid_reply(roomid, replyid, None) {
+                self.send_error(ErrCode::InvalidReply);
+                return Ok(());
+            }
 
-            let lock = self.glavra.lock().unwrap();
-            let (threshold, period) = self.get_privilege(roomid, &self.userid,
-                PrivType::SendMessage, &lock).unwrap();
+            let (threshold, since) = self.get_privilege(roomid, &self.userid,
+                PrivType::SendMessage);
 
-            if lock.conn.query("
-                        SELECT COUNT(*) >= $1
-                        FROM messages
-                        WHERE roomid = $3
-                          AND userid = $4
-                          AND tstamp BETWEEN now() - (interval '1s') * $2
-                                     AND now()",
-                    &[&threshold, &period, &roomid, &userid])
-                        .unwrap().get(0).get(0) {
+            if self.glavra.store.count_messages(roomid, userid, since)
+                    >= threshold {
                 self.send_error(ErrCode::RateLimit);
                 return Ok(());
             }
 
             let message = Message {
                 id: -1,
-                roomid: roomid,
-                userid: userid,
-                replyid: get_i32(&json, "replyid"),
-                text: text,
+                roomid,
+                userid,
+                replyid,
+                text,
                 timestamp: time::get_time()
             };
-            self.send_message(message, &lock);
+            self.send_message(message);
         }
         Ok(())
     }
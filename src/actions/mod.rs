@@ -1,8 +1,34 @@
 pub mod auth;
 pub mod register;
+pub mod sessions;
+pub mod logout;
+pub mod logoutall;
+pub mod changepassword;
+pub mod resetpassword;
+pub mod join;
+pub mod leave;
 pub mod message;
 pub mod edit;
 pub mod delete;
 pub mod vote;
 pub mod history;
+pub mod thread;
+pub mod scrollback;
+pub mod search;
+pub mod move_messages;
+pub mod markread;
+pub mod who;
+pub mod inbox;
+pub mod readinbox;
 pub mod room;
+pub mod editroom;
+pub mod archiveroom;
+pub mod setvisibility;
+pub mod createinvite;
+pub mod dm;
+pub mod dms;
+pub mod getprivs;
+pub mod setpriv;
+pub mod clearpriv;
+pub mod members;
+pub mod setrole;
@@ -0,0 +1,84 @@
+use ws;
+
+use enums::errcode::*;
+use enums::privtype::*;
+
+use protocol::*;
+
+use Server;
+
+macro_rules! require {
+    ($self_: expr, $e:expr, $err:expr) => (match $e {
+        Some(x) => x,
+        None => { $self_.send_error($err); return Ok(()); }
+    })
+}
+
+impl Server {
+    pub fn move_messages(&mut self, ids: Vec<i32>, target: i32,
+                         roomid: Option<i32>) -> ws::Result<()> {
+        let roomid = require!(self, roomid.or(self.roomid), ErrCode::NoRoomId);
+        if !self.is_joined(roomid) {
+            self.send_error(ErrCode::NotJoined);
+            return Ok(());
+        }
+        require!(self, self.userid, ErrCode::NeedLogin);
+
+        let mut ids = ids;
+        ids.sort();
+        ids.dedup();
+        if ids.is_empty() || ids.len() as i64 > self.glavra.config.history_size
+                || target == roomid {
+            self.send_error(ErrCode::Malformed);
+            return Ok(());
+        }
+
+        let room = require!(self, self.glavra.store.get_room(target),
+            ErrCode::RoomNotExist);
+        if !self.can_join(&room) {
+            self.send_error(ErrCode::NotMember);
+            return Ok(());
+        }
+        if self.is_archived(roomid) || room.archived {
+            self.send_error(ErrCode::RoomArchived);
+            return Ok(());
+        }
+        if !self.has_privilege(roomid, PrivType::MoveOut) ||
+                !self.has_privilege(target, PrivType::MoveIn) ||
+                !self.has_privilege(target, PrivType::ReadAccess) ||
+                self.is_direct(roomid) || room.direct {
+            self.send_error(ErrCode::NoPrivilege);
+            return Ok(());
+        }
+
+        // all or nothing: every message has to be from this room
+        let mut messages = Vec::with_capacity(ids.len());
+        for &id in &ids {
+            let message = require!(self, self.glavra.store.get_message(id)
+                .filter(|m| m.roomid == roomid), ErrCode::MessageNotExist);
+            messages.push(message);
+        }
+
+        self.glavra.store.move_messages(&ids, target);
+        for message in messages.iter_mut() {
+            message.roomid = target;
+        }
+
+        self.glavra.rooms.broadcast(roomid, Response::MoveOut {
+            roomid,
+            ids: ids.clone(),
+            to: target
+        }.to_json());
+        self.glavra.rooms.broadcast(target, Response::MoveIn {
+            roomid: target,
+            messages: messages.iter()
+                .map(|message| self.scrollback_frame(message)).collect(),
+            from: roomid
+        }.to_json());
+
+        self.system_message(roomid, format!("{} message{} moved to {}",
+            ids.len(), if ids.len() == 1 { "" } else { "s" }, room.name));
+
+        Ok(())
+    }
+}
@@ -0,0 +1,23 @@
+use ws;
+
+use enums::errcode::*;
+
+use Server;
+
+macro_rules! require {
+    ($self_: expr, $e:expr, $err:expr) => (match $e {
+        Some(x) => x,
+        None => { $self_.send_error($err); return Ok(()); }
+    })
+}
+
+impl Server {
+    pub fn read_inbox(&mut self, ids: Option<Vec<i32>>) -> ws::Result<()> {
+        let userid = require!(self, self.userid, ErrCode::NeedLogin);
+
+        self.glavra.store.mark_notifications_read(userid,
+            ids.as_ref().map(|ids| &ids[..]));
+
+        Ok(())
+    }
+}
@@ -0,0 +1,48 @@
+use password;
+
+use ws;
+
+use time;
+
+use enums::errcode::*;
+use protocol::Response;
+
+use Server;
+
+impl Server {
+    pub fn reset_password(&mut self, code: String, password: String)
+            -> ws::Result<()> {
+        if let Some(wait) = self.login_wait(None) {
+            self.send_retry(ErrCode::LoginThrottled, wait);
+            return Ok(());
+        }
+        if password.len() > password::MAX_LEN {
+            self.send_error(ErrCode::PasswordTooLong);
+            return Ok(());
+        }
+
+        let now = time::get_time();
+        let userid = match self.glavra.store.redeem_reset(&code, now) {
+            Some(userid) => userid,
+            None => {
+                self.login_failed(None);
+                return self.send(Response::ResetPassword { success: false });
+            }
+        };
+
+        // the code is used up either way, but the old password still works
+        let hash = match password::hash(&password, &self.glavra.config) {
+            Some(hash) => hash,
+            None => {
+                self.send_error(ErrCode::HashFailed);
+                return Ok(());
+            }
+        };
+        self.glavra.store.set_password(userid, &hash);
+        self.send(Response::ResetPassword { success: true })?;
+
+        let ids = self.glavra.store.revoke_sessions(userid, None);
+        self.end_sessions(&ids);
+        Ok(())
+    }
+}
@@ -1,11 +1,8 @@
-use util::*;
-
 use ws;
 
-use serde_json;
-use serde_json::{Value, Map};
+use enums::errcode::*;
 
-use enums::errcode::ErrCode;
+use protocol::Response;
 
 use Server;
 
@@ -16,68 +13,13 @@ macro_rules! require {
     })
 }
 
-macro_rules! rrequire {
-    ($self_: expr, $e:expr, $err:expr) => (match $e {
-        Ok(x) => x,
-        Err(_) => { $self_.send_error($err); return Ok(()); }
-    })
-}
-
 impl Server {
-    pub fn room(&mut self, json: Map<String, Value>) -> ws::Result<()> {
-        let name = require!(self, get_string(&json, "name"), ErrCode::Malformed);
-        let desc = require!(self, get_string(&json, "desc"), ErrCode::Malformed);
-
-        let lock = self.glavra.lock().unwrap();
-        let id: i32 = lock.conn.query("
-        INSERT INTO rooms (name, description) VALUES ($1, $2)
-        RETURNING id", &[&name, &desc]).unwrap().get(0).get(0);
+    pub fn room(&mut self, name: String, desc: String) -> ws::Result<()> {
+        let userid = require!(self, self.userid, ErrCode::NeedLogin);
 
-        lock.conn.execute("
-        INSERT INTO privileges (roomid, userid, privtype, threshold, period)
-        VALUES ($1, NULL, 1, 5, '5s')", &[&id]).unwrap();
-        lock.conn.execute("
-        INSERT INTO privileges (roomid, userid, privtype, threshold, period)
-        VALUES ($1, NULL, 6, 5, '5s')", &[&id]).unwrap();
-        lock.conn.execute("
-        INSERT INTO privileges (roomid, userid, privtype, threshold, period)
-        VALUES ($1, NULL, 7, 0, '0s')", &[&id]).unwrap();
-        lock.conn.execute("
-        INSERT INTO privileges (roomid, userid, privtype, threshold, period)
-        VALUES ($1, NULL, 8, 5, '5s')", &[&id]).unwrap();
-        lock.conn.execute("
-        INSERT INTO privileges (roomid, userid, privtype, threshold, period)
-        VALUES ($1, NULL, 9, 0, '0s')", &[&id]).unwrap();
-        lock.conn.execute("
-        INSERT INTO privileges (roomid, userid, privtype, threshold, period)
-        VALUES ($1, NULL, 10, 0, '0s')", &[&id]).unwrap();
-        lock.conn.execute("
-        INSERT INTO privileges (roomid, userid, privtype, threshold, period)
-        VALUES ($1, NULL, 11, 5, '5s')", &[&id]).unwrap();
-        lock.conn.execute("
-        INSERT INTO privileges (roomid, userid, privtype, threshold, period)
-        VALUES ($1, NULL, 12, 0, '0s')", &[&id]).unwrap();
-        lock.conn.execute("
-        INSERT INTO privileges (roomid, userid, privtype, threshold, period)
-        VALUES ($1, NULL, 13, 5, '5s')", &[&id]).unwrap();
-        lock.conn.execute("
-        INSERT INTO privileges (roomid, userid, privtype, threshold, period)
-        VALUES ($1, NULL, 14, 0, '0s')", &[&id]).unwrap();
-        lock.conn.execute("
-        INSERT INTO privileges (roomid, userid, privtype, threshold, period)
-        VALUES ($1, NULL, 15, 3, '1d')", &[&id]).unwrap();
-        lock.conn.execute("
-        INSERT INTO privileges (roomid, userid, privtype, threshold, period)
-        VALUES ($1, NULL, 16, 0, '0s')", &[&id]).unwrap();
-        lock.conn.execute("
-        INSERT INTO privileges (roomid, userid, privtype, threshold, period)
-        VALUES ($1, NULL, 17, 0, '0s')", &[&id]).unwrap();
+        let id = self.glavra.create_room(&name, &desc, Some(userid));
 
-        try!(self.out.send(serde_json::to_string(&json!({
-            "type": "room",
-            "success": true,
-            "id": id
-        })).unwrap()));
+        self.send(Response::Room { success: true, id })?;
 
         Ok(())
     }
@@ -0,0 +1,73 @@
+use ws;
+
+use enums::errcode::*;
+use enums::privtype::*;
+
+use protocol::*;
+
+use Server;
+
+macro_rules! require {
+    ($self_: expr, $e:expr, $err:expr) => (match $e {
+        Some(x) => x,
+        None => { $self_.send_error($err); return Ok(()); }
+    })
+}
+
+impl Server {
+    pub fn scrollback(&mut self, before: Option<i32>, after: Option<i32>,
+                      around: Option<i32>, limit: Option<i64>,
+                      roomid: Option<i32>) -> ws::Result<()> {
+        let roomid = require!(self, roomid.or(self.roomid), ErrCode::NoRoomId);
+        if !self.is_joined(roomid) {
+            self.send_error(ErrCode::NotJoined);
+            return Ok(());
+        }
+        if !self.has_privilege(roomid, PrivType::ReadAccess) {
+            self.send_error(ErrCode::NoPrivilege);
+            return Ok(());
+        }
+
+        // pages are never bigger than what we replay on connect
+        let max = self.glavra.config.history_size;
+        let limit = match limit {
+            Some(limit) if limit < 1 => {
+                self.send_error(ErrCode::Malformed);
+                return Ok(());
+            },
+            Some(limit) => limit.min(max),
+            None => max
+        };
+
+        let store = &self.glavra.store;
+        let messages = match (before, after, around) {
+            (None, None, None) => store.recent_messages(roomid, limit),
+            (Some(id), None, None) => store.messages_before(roomid, id, limit),
+            (None, Some(id), None) => store.messages_after(roomid, id, limit),
+            (None, None, Some(id)) => {
+                // the anchor has to be a real message in this room, otherwise
+                // a permalink would silently open somewhere else
+                let anchor = require!(self, store.get_message(id)
+                    .filter(|m| m.roomid == roomid), ErrCode::MessageNotExist);
+                let older = (limit - 1) / 2;
+                let mut messages = store.messages_before(roomid, id, older);
+                messages.push(anchor);
+                messages.extend(store.messages_after(roomid, id,
+                    limit - 1 - older));
+                messages
+            },
+            _ => {
+                self.send_error(ErrCode::Malformed);
+                return Ok(());
+            }
+        };
+
+        let messages = messages.iter()
+            .map(|message| self.scrollback_frame(message)).collect();
+        self.send(Response::Scrollback {
+            roomid,
+            messages
+        })?;
+        Ok(())
+    }
+}
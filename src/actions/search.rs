@@ -0,0 +1,84 @@
+use ws;
+
+use time::Timespec;
+
+use enums::errcode::*;
+use enums::privtype::*;
+
+use types::search::*;
+use protocol::*;
+
+use Server;
+
+macro_rules! require {
+    ($self_: expr, $e:expr, $err:expr) => (match $e {
+        Some(x) => x,
+        None => { $self_.send_error($err); return Ok(()); }
+    })
+}
+
+impl Server {
+    #[allow(clippy::too_many_arguments)]
+    pub fn search(&mut self, query: String, roomid: Option<i32>,
+                  userid: Option<i32>, since: Option<i64>, until: Option<i64>,
+                  hasreply: Option<bool>, limit: Option<i64>)
+            -> ws::Result<()> {
+        if query.trim().is_empty() {
+            self.send_error(ErrCode::Malformed);
+            return Ok(());
+        }
+
+        let max = self.glavra.config.history_size;
+        let limit = match limit {
+            Some(limit) if limit < 1 => {
+                self.send_error(ErrCode::Malformed);
+                return Ok(());
+            },
+            Some(limit) => limit.min(max),
+            None => max
+        };
+
+        let roomids: Vec<i32> = match roomid {
+            Some(roomid) => {
+                let room = require!(self, self.glavra.store.get_room(roomid),
+                    ErrCode::RoomNotExist);
+                if !self.can_join(&room) {
+                    self.send_error(ErrCode::NotMember);
+                    return Ok(());
+                }
+                vec![roomid]
+            },
+            None => self.glavra.store.list_rooms().into_iter()
+                .filter(|room| self.can_join(room))
+                .map(|room| room.id).collect()
+        };
+        // unreadable rooms are quietly left out rather than being an error,
+        // so that searching everywhere still works
+        let roomids = roomids.into_iter()
+            .filter(|&roomid| self.has_privilege(roomid, PrivType::ReadAccess))
+            .collect();
+
+        let query = SearchQuery {
+            text: query,
+            roomids,
+            userid,
+            since: since.map(|sec| Timespec::new(sec, 0)),
+            until: until.map(|sec| Timespec::new(sec, 0)),
+            hasreply,
+            limit
+        };
+        let results = self.glavra.store.search(&query).into_iter()
+            .map(|result| SearchFrame {
+                id: result.message.id,
+                roomid: result.message.roomid,
+                userid: result.message.userid,
+                replyid: result.message.replyid,
+                username: self.get_username(result.message.userid).unwrap(),
+                snippet: result.snippet,
+                timestamp: result.message.timestamp.sec,
+                rank: result.rank
+            }).collect();
+        self.send(Response::Search { results })?;
+        Ok(())
+    }
+}
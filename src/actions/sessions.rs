@@ -0,0 +1,30 @@
+use ws;
+
+use time;
+
+use enums::errcode::*;
+
+use protocol::*;
+
+use Server;
+
+macro_rules! require {
+    ($self_: expr, $e:expr, $err:expr) => (match $e {
+        Some(x) => x,
+        None => { $self_.send_error($err); return Ok(()); }
+    })
+}
+
+impl Server {
+    pub fn sessions(&mut self) -> ws::Result<()> {
+        let userid = require!(self, self.userid, ErrCode::NeedLogin);
+
+        let now = time::get_time();
+        let sessions = self.glavra.store.list_sessions(userid).iter()
+            .filter(|session| !session.expired(now))
+            .map(|session| self.session_frame(session)).collect();
+        self.send(Response::Sessions { sessions })?;
+
+        Ok(())
+    }
+}
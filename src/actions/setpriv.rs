@@ -0,0 +1,68 @@
+use ws;
+
+use enums::errcode::*;
+use enums::privtype::*;
+
+use types::privilege::*;
+use protocol::*;
+
+use Server;
+
+macro_rules! require {
+    ($self_: expr, $e:expr, $err:expr) => (match $e {
+        Some(x) => x,
+        None => { $self_.send_error($err); return Ok(()); }
+    })
+}
+
+impl Server {
+    pub fn set_priv(&mut self, privtype: String, userid: Option<i32>,
+                    threshold: i32, period: i32, roomid: Option<i32>)
+            -> ws::Result<()> {
+        let roomid = require!(self, roomid.or(self.roomid), ErrCode::NoRoomId);
+        if !self.is_joined(roomid) {
+            self.send_error(ErrCode::NotJoined);
+            return Ok(());
+        }
+        require!(self, self.userid, ErrCode::NeedLogin);
+
+        if !self.has_privilege(roomid, PrivType::ModifyPrivs) {
+            self.send_error(ErrCode::NoPrivilege);
+            return Ok(());
+        }
+
+        let privtype = require!(self, PrivType::from_name(&privtype),
+            ErrCode::Malformed);
+        if threshold < 0 || period < 0 {
+            self.send_error(ErrCode::Malformed);
+            return Ok(());
+        }
+        if let Some(userid) = userid {
+            require!(self, self.glavra.store.get_user(userid),
+                ErrCode::UserNotExist);
+            if !self.can_modify(roomid, userid) {
+                self.send_error(ErrCode::NoPrivilege);
+                return Ok(());
+            }
+        }
+
+        self.glavra.store.set_privilege(roomid, userid, privtype, threshold,
+            period);
+
+        let frame = self.privilege_frame(&Privilege {
+            roomid,
+            userid,
+            privtype,
+            threshold,
+            period
+        });
+        self.glavra.rooms.broadcast(roomid,
+            Response::Privilege(frame).to_json());
+
+        if privtype == PrivType::ReadAccess && threshold == 0 {
+            self.recheck_access(roomid, userid);
+        }
+
+        Ok(())
+    }
+}
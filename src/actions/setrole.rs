@@ -0,0 +1,61 @@
+use ws;
+
+use enums::errcode::*;
+use enums::privtype::*;
+use enums::role::*;
+
+use protocol::*;
+
+use Server;
+use RECHECK;
+
+macro_rules! require {
+    ($self_: expr, $e:expr, $err:expr) => (match $e {
+        Some(x) => x,
+        None => { $self_.send_error($err); return Ok(()); }
+    })
+}
+
+impl Server {
+    pub fn set_role(&mut self, userid: i32, role: String, roomid: Option<i32>)
+            -> ws::Result<()> {
+        let roomid = require!(self, roomid.or(self.roomid), ErrCode::NoRoomId);
+        if !self.is_joined(roomid) {
+            self.send_error(ErrCode::NotJoined);
+            return Ok(());
+        }
+        let me = require!(self, self.userid, ErrCode::NeedLogin);
+
+        let role = require!(self, Role::from_name(&role), ErrCode::Malformed);
+        require!(self, self.glavra.store.get_user(userid),
+            ErrCode::UserNotExist);
+
+        // nobody changes their own role, so a room can't be left without an
+        // owner by accident
+        let store = &self.glavra.store;
+        let is_owner = store.get_role(roomid, me) == Some(Role::Owner);
+        let target = store.get_role(roomid, userid);
+        if userid == me || self.is_direct(roomid) ||
+                !self.has_privilege(roomid, PrivType::ModifyPrivs) ||
+                (!is_owner && (role == Role::Owner ||
+                               target == Some(Role::Owner))) {
+            self.send_error(ErrCode::NoPrivilege);
+            return Ok(());
+        }
+
+        store.set_role(roomid, userid, role);
+
+        self.glavra.rooms.broadcast(roomid, Response::Role {
+            roomid,
+            userid,
+            role: role.name().to_string()
+        }.to_json());
+
+        // and banned users stop getting anything from the room
+        if role == Role::Banned {
+            self.glavra.users.wake(userid, RECHECK);
+        }
+
+        Ok(())
+    }
+}
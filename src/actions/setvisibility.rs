@@ -0,0 +1,49 @@
+use ws;
+
+use enums::errcode::*;
+use enums::privtype::*;
+use enums::visibility::*;
+
+use Server;
+
+macro_rules! require {
+    ($self_: expr, $e:expr, $err:expr) => (match $e {
+        Some(x) => x,
+        None => { $self_.send_error($err); return Ok(()); }
+    })
+}
+
+impl Server {
+    pub fn set_visibility(&mut self, visibility: String, roomid: Option<i32>)
+            -> ws::Result<()> {
+        let roomid = require!(self, roomid.or(self.roomid), ErrCode::NoRoomId);
+        if !self.is_joined(roomid) {
+            self.send_error(ErrCode::NotJoined);
+            return Ok(());
+        }
+        require!(self, self.userid, ErrCode::NeedLogin);
+
+        if !self.has_privilege(roomid, PrivType::ModifyRoom) ||
+                self.is_direct(roomid) {
+            self.send_error(ErrCode::NoPrivilege);
+            return Ok(());
+        }
+
+        let visibility = require!(self, Visibility::from_name(&visibility),
+            ErrCode::Malformed);
+        let mut room = require!(self, self.glavra.store.get_room(roomid),
+            ErrCode::RoomNotExist);
+        if room.archived {
+            self.send_error(ErrCode::RoomArchived);
+            return Ok(());
+        }
+
+        self.glavra.store.set_visibility(roomid, visibility);
+        room.visibility = visibility;
+
+        self.glavra.rooms.broadcast(roomid,
+            self.roominfo_frame(room).to_json());
+
+        Ok(())
+    }
+}
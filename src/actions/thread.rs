@@ -0,0 +1,61 @@
+use ws;
+
+use enums::errcode::*;
+use enums::privtype::*;
+
+use protocol::*;
+
+use Server;
+
+macro_rules! require {
+    ($self_: expr, $e:expr, $err:expr) => (match $e {
+        Some(x) => x,
+        None => { $self_.send_error($err); return Ok(()); }
+    })
+}
+
+impl Server {
+    pub fn thread(&mut self, id: i32) -> ws::Result<()> {
+        let message = require!(self, self.glavra.store.get_message(id),
+            ErrCode::MessageNotExist);
+        let room = require!(self, self.glavra.store.get_room(message.roomid),
+            ErrCode::RoomNotExist);
+        if !self.can_join(&room) {
+            self.send_error(ErrCode::NotMember);
+            return Ok(());
+        }
+        if !self.has_privilege(message.roomid, PrivType::ReadAccess) {
+            self.send_error(ErrCode::NoPrivilege);
+            return Ok(());
+        }
+
+        // replies always point further back, so this ends; messages that
+        // were moved elsewhere since cut the chain short
+        let mut ancestors = Vec::new();
+        let mut child = message.clone();
+        loop {
+            let parent = match child.replyid
+                    .and_then(|id| self.glavra.store.get_message(id)) {
+                Some(parent) if parent.roomid == message.roomid &&
+                    parent.id < child.id => parent,
+                _ => break
+            };
+            ancestors.push(parent.clone());
+            child = parent;
+        }
+        ancestors.reverse();
+
+        let replies = self.glavra.store.replies(id, message.roomid);
+
+        self.send(Response::Thread {
+            roomid: message.roomid,
+            ancestors: ancestors.iter()
+                .map(|message| self.scrollback_frame(message)).collect(),
+            message: self.scrollback_frame(&message),
+            replies: replies.iter()
+                .map(|message| self.scrollback_frame(message)).collect()
+        })?;
+
+        Ok(())
+    }
+}
@@ -1,9 +1,5 @@
-use util::*;
-
 use ws;
 
-use serde_json::{Value, Map};
-
 use time;
 
 use enums::errcode::*;
@@ -20,26 +16,26 @@ macro_rules! require {
     })
 }
 
-macro_rules! rrequire {
-    ($self_: expr, $e:expr, $err:expr) => (match $e {
-        Ok(x) => x,
-        Err(_) => { $self_.send_error($err); return Ok(()); }
-    })
-}
-
 impl Server {
-    pub fn vote(&mut self, json: Map<String, Value>) -> ws::Result<()> {
-        let i_votetype = require!(self, get_i32(&json, "votetype"),
+    pub fn vote(&mut self, id: i32, votetype: i32) -> ws::Result<()> {
+        let votetype = require!(self, int_to_votetype(votetype),
             ErrCode::Malformed);
-        let votetype = require!(self, int_to_votetype(i_votetype),
+
+        let userid = require!(self, self.userid, ErrCode::NeedLogin);
+        let message = require!(self, self.glavra.store.get_message(id),
             ErrCode::Malformed);
+        let roomid = message.roomid;
+        if !self.is_joined(roomid) {
+            self.send_error(ErrCode::NotJoined);
+            return Ok(());
+        }
+
+        if self.is_archived(roomid) {
+            self.send_error(ErrCode::RoomArchived);
+            return Ok(());
+        }
 
-        let lock = self.glavra.lock().unwrap();
-        let id = require!(self, get_i32(&json, "messageid"), ErrCode::Malformed);
-        let roomid = require!(self, self.roomid, ErrCode::NoRoomId);
-        let userid = require!(self, self.userid.clone(), ErrCode::NeedLogin);
-        let muserid = rrequire!(self, self.get_sender(id, &lock), ErrCode::Malformed);
-        let own = userid == muserid;
+        let own = userid == message.userid;
 
         let privtype = match votetype {
             VoteType::Upvote   => if own { PrivType::UpvoteOwn      }
@@ -51,20 +47,11 @@ impl Server {
             VoteType::Pin      => if own { PrivType::PinOwn         }
                                     else { PrivType::PinOthers      }
         };
-        let (threshold, period) = self.get_privilege(roomid, &self.userid,
-            privtype, &lock).unwrap();
+        let (threshold, since) = self.get_privilege(roomid, &self.userid,
+            privtype);
 
-        if lock.conn.query("
-                    SELECT COUNT(*) >= $1
-                    FROM votes v
-                    INNER JOIN messages m ON m.id = v.messageid
-                    WHERE m.roomid = $3
-                      AND v.userid = $4
-                      AND v.votetype = $5
-                      AND v.tstamp BETWEEN now() - (interval '1s') * $2
-                                   AND now()",
-                &[&threshold, &period, &roomid, &userid, &i_votetype])
-                    .unwrap().get(0).get(0) {
+        if self.glavra.store.count_votes(roomid, userid, &votetype, since)
+                >= threshold {
             self.send_error(ErrCode::RateLimit);
             return Ok(());
         }
@@ -72,15 +59,16 @@ impl Server {
         let vote = Vote {
             id: -1,
             messageid: id,
-            userid: userid,
+            userid,
             votetype: votetype.clone(),
             timestamp: time::get_time()
         };
-        self.send_vote(vote, &lock);
+        self.send_vote(vote, roomid);
 
         match &votetype {
             &VoteType::Star | &VoteType::Pin => {
-                try!(self.out.broadcast(self.starboard_json(votetype, &lock)));
+                self.glavra.rooms.broadcast(roomid,
+                    self.starboard_frame(roomid, votetype).to_json());
             },
             _ => {}
         }
@@ -0,0 +1,34 @@
+use ws;
+
+use enums::errcode::*;
+
+use protocol::*;
+
+use Server;
+
+macro_rules! require {
+    ($self_: expr, $e:expr, $err:expr) => (match $e {
+        Some(x) => x,
+        None => { $self_.send_error($err); return Ok(()); }
+    })
+}
+
+impl Server {
+    pub fn who(&mut self, roomid: Option<i32>) -> ws::Result<()> {
+        let roomid = require!(self, roomid.or(self.roomid), ErrCode::NoRoomId);
+        if !self.is_joined(roomid) {
+            self.send_error(ErrCode::NotJoined);
+            return Ok(());
+        }
+
+        let users = self.glavra.presence.who(roomid).into_iter()
+            .filter_map(|userid| self.get_username(userid)
+                .map(|username| UserFrame {
+                    id: userid,
+                    username
+                })).collect();
+        self.send(Response::Who { roomid, users })?;
+
+        Ok(())
+    }
+}
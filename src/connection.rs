@@ -0,0 +1,97 @@
+use ws;
+use ws::Handler;
+use ws::util::Token;
+
+use enums::errcode::ErrCode;
+use store::Unavailable;
+
+use Server;
+
+use std::panic;
+use std::panic::AssertUnwindSafe;
+use std::sync::mpsc;
+use std::thread;
+
+// everything ws would otherwise have called the Server's handler with
+enum Event {
+    Open(ws::Handshake),
+    Message(ws::Message),
+    Timeout(Token),
+    Close(ws::CloseCode, String)
+}
+
+// what the event loop actually holds for each connection: it hands every
+// event to a thread of the connection's own, where the Server does the work,
+// so that waiting on the store only ever holds up the socket that asked
+pub struct Connection {
+    events: mpsc::Sender<Event>
+}
+
+impl Connection {
+
+    pub fn spawn(server: Server) -> Connection {
+        let (events, queue) = mpsc::channel();
+        thread::spawn(move || run(server, queue));
+        Connection { events }
+    }
+
+    // the thread only goes away early if the Server panicked
+    fn forward(&self, event: Event) -> ws::Result<()> {
+        self.events.send(event).map_err(|_| ws::Error::new(
+            ws::ErrorKind::Internal, "connection thread is gone"))
+    }
+
+}
+
+impl ws::Handler for Connection {
+
+    fn on_open(&mut self, hs: ws::Handshake) -> ws::Result<()> {
+        self.forward(Event::Open(hs))
+    }
+
+    fn on_message(&mut self, msg: ws::Message) -> ws::Result<()> {
+        self.forward(Event::Message(msg))
+    }
+
+    fn on_close(&mut self, code: ws::CloseCode, reason: &str) {
+        let _ = self.forward(Event::Close(code, reason.to_string()));
+    }
+
+    fn on_timeout(&mut self, token: Token) -> ws::Result<()> {
+        self.forward(Event::Timeout(token))
+    }
+
+}
+
+// events are handled one at a time and in order, the same as they would be
+// on the event loop
+fn run(mut server: Server, queue: mpsc::Receiver<Event>) {
+    for event in queue {
+        let closed = matches!(event, Event::Close(..));
+        let result = panic::catch_unwind(AssertUnwindSafe(|| match event {
+            Event::Open(hs) => server.on_open(hs),
+            Event::Message(msg) => server.on_message(msg),
+            Event::Timeout(token) => server.on_timeout(token),
+            Event::Close(code, reason) => {
+                server.on_close(code, &reason);
+                Ok(())
+            }
+        }));
+        match result {
+            Ok(Ok(())) => {},
+            // what ws does when a handler fails
+            Ok(Err(e)) => {
+                error!("closing connection: {}", e);
+                let _ = server.out.close(ws::CloseCode::Error);
+            },
+            // the store gave up on this request, but the next one might
+            // have better luck
+            Err(cause) => if cause.is::<Unavailable>() {
+                server.send_error(ErrCode::StoreUnavailable);
+            } else {
+                panic::resume_unwind(cause);
+            }
+        }
+        if closed { break; }
+    }
+}
@@ -1,4 +1,9 @@
-#[derive(Copy, Clone)]
+use serde::{Serialize, Serializer, Deserialize, Deserializer};
+use serde::de::Error;
+
+// these go over the wire as their numeric value, so only ever add new ones
+// at the end (and to ALL below)
+#[derive(Copy, Clone, Debug, PartialEq)]
 pub enum ErrCode {
     NeedLogin,
     Malformed,
@@ -11,5 +16,70 @@ pub enum ErrCode {
     UsernameTooLong,
     RateLimit,
     InvalidUserId,
-    UserNotExist
+    UserNotExist,
+    MessageNotExist,
+    NoPrivilege,
+    RoomArchived,
+    NotMember,
+    InvalidReply,
+    NotJoined,
+    BadToken,
+    TokenExpired,
+    SessionNotExist,
+    LoginThrottled,
+    RegisterThrottled,
+    StoreUnavailable,
+    PasswordTooLong,
+    HashFailed
+}
+
+const ALL: &[ErrCode] = &[
+    ErrCode::NeedLogin,
+    ErrCode::Malformed,
+    ErrCode::EmptyMsg,
+    ErrCode::EditDeleted,
+    ErrCode::BadReqUrl,
+    ErrCode::NoRoomId,
+    ErrCode::InvalidRoomId,
+    ErrCode::RoomNotExist,
+    ErrCode::UsernameTooLong,
+    ErrCode::RateLimit,
+    ErrCode::InvalidUserId,
+    ErrCode::UserNotExist,
+    ErrCode::MessageNotExist,
+    ErrCode::NoPrivilege,
+    ErrCode::RoomArchived,
+    ErrCode::NotMember,
+    ErrCode::InvalidReply,
+    ErrCode::NotJoined,
+    ErrCode::BadToken,
+    ErrCode::TokenExpired,
+    ErrCode::SessionNotExist,
+    ErrCode::LoginThrottled,
+    ErrCode::RegisterThrottled,
+    ErrCode::StoreUnavailable,
+    ErrCode::PasswordTooLong,
+    ErrCode::HashFailed
+];
+
+impl ErrCode {
+    pub fn from_i32(code: i32) -> Option<ErrCode> {
+        if code < 0 { None } else { ALL.get(code as usize).cloned() }
+    }
+}
+
+impl Serialize for ErrCode {
+    fn serialize<S: Serializer>(&self, serializer: S)
+            -> Result<S::Ok, S::Error> {
+        serializer.serialize_i32(*self as i32)
+    }
+}
+
+impl<'de> Deserialize<'de> for ErrCode {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D)
+            -> Result<ErrCode, D::Error> {
+        let code = i32::deserialize(deserializer)?;
+        ErrCode::from_i32(code).ok_or_else(||
+            D::Error::custom(format!("unknown error code {}", code)))
+    }
 }
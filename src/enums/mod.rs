@@ -1,2 +1,4 @@
 pub mod errcode;
 pub mod privtype;
+pub mod role;
+pub mod visibility;
@@ -1,4 +1,4 @@
-#[derive(Copy, Clone)]
+#[derive(Copy, Clone, PartialEq)]
 pub enum PrivType {
     ReadAccess,
     SendMessage,
@@ -19,3 +19,78 @@ pub enum PrivType {
     PinOwn,
     PinOthers
 }
+
+// in the order of their values in the privileges.privtype column
+const ALL: &[PrivType] = &[
+    PrivType::ReadAccess,
+    PrivType::SendMessage,
+    PrivType::MoveIn,
+    PrivType::MoveOut,
+    PrivType::ModifyRoom,
+    PrivType::ModifyPrivs,
+    PrivType::EditOwn,
+    PrivType::EditOthers,
+    PrivType::DeleteOwn,
+    PrivType::DeleteOthers,
+    PrivType::UpvoteOwn,
+    PrivType::UpvoteOthers,
+    PrivType::DownvoteOwn,
+    PrivType::DownvoteOthers,
+    PrivType::StarOwn,
+    PrivType::StarOthers,
+    PrivType::PinOwn,
+    PrivType::PinOthers
+];
+
+impl PrivType {
+    pub fn from_i32(privtype: i32) -> Option<PrivType> {
+        if privtype < 0 { None } else { ALL.get(privtype as usize).cloned() }
+    }
+
+    pub fn from_name(name: &str) -> Option<PrivType> {
+        match &name.to_lowercase()[..] {
+            "readaccess"     => Some(PrivType::ReadAccess),
+            "sendmessage"    => Some(PrivType::SendMessage),
+            "movein"         => Some(PrivType::MoveIn),
+            "moveout"        => Some(PrivType::MoveOut),
+            "modifyroom"     => Some(PrivType::ModifyRoom),
+            "modifyprivs"    => Some(PrivType::ModifyPrivs),
+            "editown"        => Some(PrivType::EditOwn),
+            "editothers"     => Some(PrivType::EditOthers),
+            "deleteown"      => Some(PrivType::DeleteOwn),
+            "deleteothers"   => Some(PrivType::DeleteOthers),
+            "upvoteown"      => Some(PrivType::UpvoteOwn),
+            "upvoteothers"   => Some(PrivType::UpvoteOthers),
+            "downvoteown"    => Some(PrivType::DownvoteOwn),
+            "downvoteothers" => Some(PrivType::DownvoteOthers),
+            "starown"        => Some(PrivType::StarOwn),
+            "starothers"     => Some(PrivType::StarOthers),
+            "pinown"         => Some(PrivType::PinOwn),
+            "pinothers"      => Some(PrivType::PinOthers),
+            _ => None
+        }
+    }
+
+    pub fn name(&self) -> &'static str {
+        match *self {
+            PrivType::ReadAccess     => "readaccess",
+            PrivType::SendMessage    => "sendmessage",
+            PrivType::MoveIn         => "movein",
+            PrivType::MoveOut        => "moveout",
+            PrivType::ModifyRoom     => "modifyroom",
+            PrivType::ModifyPrivs    => "modifyprivs",
+            PrivType::EditOwn        => "editown",
+            PrivType::EditOthers     => "editothers",
+            PrivType::DeleteOwn      => "deleteown",
+            PrivType::DeleteOthers   => "deleteothers",
+            PrivType::UpvoteOwn      => "upvoteown",
+            PrivType::UpvoteOthers   => "upvoteothers",
+            PrivType::DownvoteOwn    => "downvoteown",
+            PrivType::DownvoteOthers => "downvoteothers",
+            PrivType::StarOwn        => "starown",
+            PrivType::StarOthers     => "starothers",
+            PrivType::PinOwn         => "pinown",
+            PrivType::PinOthers      => "pinothers"
+        }
+    }
+}
@@ -0,0 +1,64 @@
+use enums::privtype::*;
+
+// what someone is in a particular room; users without a row in the members
+// table have no role there and aren't members of it, so they only get in if
+// the room isn't invite-only
+//
+// the discriminants are what ends up in the members.role column
+#[derive(Copy, Clone, PartialEq, Debug)]
+pub enum Role {
+    Owner,
+    Moderator,
+    Member,
+    Banned
+}
+
+const ALL: &[Role] = &[
+    Role::Owner,
+    Role::Moderator,
+    Role::Member,
+    Role::Banned
+];
+
+impl Role {
+    pub fn from_i32(role: i32) -> Option<Role> {
+        if role < 0 { None } else { ALL.get(role as usize).cloned() }
+    }
+
+    pub fn from_name(name: &str) -> Option<Role> {
+        match &name.to_lowercase()[..] {
+            "owner"     => Some(Role::Owner),
+            "moderator" => Some(Role::Moderator),
+            "member"    => Some(Role::Member),
+            "banned"    => Some(Role::Banned),
+            _ => None
+        }
+    }
+
+    pub fn name(&self) -> &'static str {
+        match *self {
+            Role::Owner     => "owner",
+            Role::Moderator => "moderator",
+            Role::Member    => "member",
+            Role::Banned    => "banned"
+        }
+    }
+
+    // the (threshold, period) this role implies for a privilege, if any; it
+    // sits between a user's own privilege rows and the room-wide ones, so
+    // None means "whatever applies to everyone else"
+    pub fn privilege(&self, privtype: PrivType) -> Option<(i64, f64)> {
+        let unlimited = Some((i64::MAX, 0.0));
+        match *self {
+            Role::Owner => unlimited,
+            Role::Moderator => match privtype {
+                PrivType::ModifyRoom | PrivType::MoveIn | PrivType::MoveOut |
+                PrivType::EditOthers | PrivType::DeleteOthers |
+                PrivType::PinOthers => unlimited,
+                _ => None
+            },
+            Role::Member => None,
+            Role::Banned => Some((0, 0.0))
+        }
+    }
+}
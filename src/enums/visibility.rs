@@ -0,0 +1,41 @@
+// who can find and join a room; the discriminants are what ends up in the
+// rooms.visibility column
+#[derive(Copy, Clone, PartialEq, Debug)]
+pub enum Visibility {
+    // listed in queryrooms and joinable by anyone
+    Public,
+    // joinable by anyone who knows the id, but only listed for members
+    Unlisted,
+    // only members can see or join it; everyone else needs an invite
+    Invite
+}
+
+const ALL: &[Visibility] = &[
+    Visibility::Public,
+    Visibility::Unlisted,
+    Visibility::Invite
+];
+
+impl Visibility {
+    pub fn from_i32(visibility: i32) -> Option<Visibility> {
+        if visibility < 0 { None }
+        else { ALL.get(visibility as usize).cloned() }
+    }
+
+    pub fn from_name(name: &str) -> Option<Visibility> {
+        match &name.to_lowercase()[..] {
+            "public"   => Some(Visibility::Public),
+            "unlisted" => Some(Visibility::Unlisted),
+            "invite"   => Some(Visibility::Invite),
+            _ => None
+        }
+    }
+
+    pub fn name(&self) -> &'static str {
+        match *self {
+            Visibility::Public   => "public",
+            Visibility::Unlisted => "unlisted",
+            Visibility::Invite   => "invite"
+        }
+    }
+}
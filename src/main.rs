@@ -1,9 +1,74 @@
 extern crate glavra;
 use glavra::*;
+use glavra::config::{Config, StoreKind};
 
 use std::env;
+use std::process;
 
 fn main() {
-    let args: Vec<String> = env::args().collect();
-    Glavra::start("0.0.0.0:3012", args.contains(&String::from("-r")));
+    let mut args: Vec<String> = env::args().skip(1).collect();
+
+    let migrate = args.first().map(|s| &s[..]) == Some("migrate");
+    let mut reset = false;
+    if migrate {
+        args.remove(0);
+        reset = args.contains(&String::from("--reset"));
+        args.retain(|arg| arg != "--reset");
+    }
+
+    // glavra resetpassword <username> prints a reset code for that user
+    let resetuser = if args.first().map(|s| &s[..]) == Some("resetpassword") {
+        args.remove(0);
+        if args.is_empty() {
+            eprintln!("usage: glavra resetpassword <username> [options]");
+            process::exit(2);
+        }
+        Some(args.remove(0))
+    } else { None };
+
+    let config = match Config::load(&args) {
+        Ok(config) => config,
+        Err(e) => {
+            eprintln!("glavra: {}", e);
+            process::exit(2);
+        }
+    };
+    logger::init(config.log_level);
+
+    if migrate {
+        if let Err(e) = Glavra::migrate(&config, reset) {
+            eprintln!("glavra: {}", e);
+            process::exit(1);
+        }
+        return;
+    }
+
+    if let Some(username) = resetuser {
+        // the code has to outlive this process, which rules out the memory
+        // store
+        if config.store != StoreKind::Postgres {
+            eprintln!("glavra: resetpassword needs the postgres store");
+            process::exit(2);
+        }
+        let glavra = match Glavra::open(config) {
+            Ok(glavra) => glavra,
+            Err(e) => {
+                eprintln!("glavra: {}", e);
+                process::exit(1);
+            }
+        };
+        match glavra.reset_code(&username) {
+            Some(code) => println!("{}", code),
+            None => {
+                eprintln!("glavra: no user named {}", username);
+                process::exit(1);
+            }
+        }
+        return;
+    }
+
+    if let Err(e) = Glavra::start(config) {
+        eprintln!("glavra: {}", e);
+        process::exit(1);
+    }
 }
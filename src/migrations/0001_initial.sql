@@ -0,0 +1,82 @@
+CREATE TABLE rooms (
+id          SERIAL PRIMARY KEY,
+name        TEXT NOT NULL,
+description TEXT NOT NULL
+);
+
+CREATE TABLE messages (
+id          SERIAL PRIMARY KEY,
+roomid      INT NOT NULL,
+userid      INT NOT NULL,
+replyid     INT,
+text        TEXT NOT NULL,
+tstamp      TIMESTAMP NOT NULL
+);
+
+CREATE TABLE users (
+id          SERIAL PRIMARY KEY,
+username    TEXT NOT NULL UNIQUE,
+salt        BYTEA NOT NULL,
+hash        BYTEA NOT NULL,
+theme       TEXT NOT NULL
+);
+
+CREATE TABLE tokens (
+userid      INT NOT NULL,
+token       TEXT NOT NULL
+);
+
+CREATE TABLE votes (
+id          SERIAL PRIMARY KEY,
+messageid   INT NOT NULL,
+userid      INT NOT NULL,
+votetype    INT NOT NULL,
+tstamp      TIMESTAMP NOT NULL
+);
+
+CREATE TABLE history (
+id          SERIAL PRIMARY KEY,
+messageid   INT NOT NULL,
+replyid     INT,
+text        TEXT NOT NULL,
+tstamp      TIMESTAMP NOT NULL
+);
+
+CREATE TABLE privileges (
+id          SERIAL PRIMARY KEY,
+roomid      INT NOT NULL,
+userid      INT,
+privtype    INT NOT NULL,
+threshold   INT NOT NULL,
+period      INTERVAL NOT NULL
+);
+
+INSERT INTO rooms (name, description)
+VALUES ('Glavra', 'Glavra chatroom');
+
+INSERT INTO privileges (roomid, userid, privtype, threshold, period)
+VALUES (1, NULL, 1, 5, '5s'); -- SendMessage
+INSERT INTO privileges (roomid, userid, privtype, threshold, period)
+VALUES (1, NULL, 6, 5, '5s'); -- EditOwn
+INSERT INTO privileges (roomid, userid, privtype, threshold, period)
+VALUES (1, NULL, 7, 0, '0s'); -- EditOthers
+INSERT INTO privileges (roomid, userid, privtype, threshold, period)
+VALUES (1, NULL, 8, 5, '5s'); -- DeleteOwn
+INSERT INTO privileges (roomid, userid, privtype, threshold, period)
+VALUES (1, NULL, 9, 0, '0s'); -- DeleteOthers
+INSERT INTO privileges (roomid, userid, privtype, threshold, period)
+VALUES (1, NULL, 10, 0, '0s'); -- UpvoteOwn
+INSERT INTO privileges (roomid, userid, privtype, threshold, period)
+VALUES (1, NULL, 11, 5, '5s'); -- UpvoteOthers
+INSERT INTO privileges (roomid, userid, privtype, threshold, period)
+VALUES (1, NULL, 12, 0, '0s'); -- DownvoteOwn
+INSERT INTO privileges (roomid, userid, privtype, threshold, period)
+VALUES (1, NULL, 13, 5, '5s'); -- DownvoteOthers
+INSERT INTO privileges (roomid, userid, privtype, threshold, period)
+VALUES (1, NULL, 14, 0, '0s'); -- StarOwn
+INSERT INTO privileges (roomid, userid, privtype, threshold, period)
+VALUES (1, NULL, 15, 3, '1d'); -- StarOthers
+INSERT INTO privileges (roomid, userid, privtype, threshold, period)
+VALUES (1, NULL, 16, 0, '0s'); -- PinOwn
+INSERT INTO privileges (roomid, userid, privtype, threshold, period)
+VALUES (1, NULL, 17, 0, '0s'); -- PinOthers
@@ -0,0 +1,10 @@
+ALTER TABLE messages ADD COLUMN textsearch TSVECTOR;
+
+UPDATE messages SET textsearch = to_tsvector('pg_catalog.english', text);
+
+CREATE TRIGGER messages_textsearch
+BEFORE INSERT OR UPDATE OF text ON messages
+FOR EACH ROW EXECUTE PROCEDURE
+tsvector_update_trigger(textsearch, 'pg_catalog.english', text);
+
+CREATE INDEX messages_textsearch_idx ON messages USING GIN (textsearch);
@@ -0,0 +1,6 @@
+CREATE TABLE members (
+roomid      INT NOT NULL,
+userid      INT NOT NULL,
+role        INT NOT NULL,
+PRIMARY KEY (roomid, userid)
+);
@@ -0,0 +1,8 @@
+ALTER TABLE rooms ADD COLUMN visibility INT NOT NULL DEFAULT 0;
+
+CREATE TABLE invites (
+token       TEXT PRIMARY KEY,
+roomid      INT NOT NULL,
+userid      INT NOT NULL,
+tstamp      TIMESTAMP NOT NULL
+);
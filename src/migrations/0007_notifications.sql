@@ -0,0 +1,11 @@
+CREATE TABLE notifications (
+id          SERIAL PRIMARY KEY,
+userid      INT NOT NULL,
+messageid   INT NOT NULL,
+kind        INT NOT NULL,
+tstamp      TIMESTAMP NOT NULL,
+read        BOOLEAN NOT NULL DEFAULT FALSE,
+UNIQUE (userid, messageid, kind)
+);
+
+CREATE INDEX notifications_unread_idx ON notifications (userid) WHERE NOT read;
@@ -0,0 +1,6 @@
+CREATE TABLE readmarkers (
+userid      INT NOT NULL,
+roomid      INT NOT NULL,
+messageid   INT NOT NULL,
+PRIMARY KEY (userid, roomid)
+);
@@ -0,0 +1,12 @@
+ALTER TABLE tokens ADD COLUMN id SERIAL PRIMARY KEY;
+ALTER TABLE tokens ADD COLUMN label TEXT NOT NULL DEFAULT '';
+ALTER TABLE tokens ADD COLUMN created TIMESTAMP NOT NULL
+    DEFAULT (now() AT TIME ZONE 'utc');
+ALTER TABLE tokens ADD COLUMN lastused TIMESTAMP NOT NULL
+    DEFAULT (now() AT TIME ZONE 'utc');
+ALTER TABLE tokens ADD COLUMN expires TIMESTAMP NOT NULL
+    DEFAULT (now() AT TIME ZONE 'utc') + INTERVAL '30 days';
+ALTER TABLE tokens ADD COLUMN revoked BOOLEAN NOT NULL DEFAULT FALSE;
+
+CREATE UNIQUE INDEX tokens_token_idx ON tokens (token);
+CREATE INDEX tokens_userid_idx ON tokens (userid);
@@ -0,0 +1,9 @@
+ALTER TABLE users ADD COLUMN password TEXT;
+
+UPDATE users SET password = '$bcrypt$c=10$' ||
+    rtrim(encode(salt, 'base64'), '=') || '$' ||
+    rtrim(encode(hash, 'base64'), '=');
+
+ALTER TABLE users ALTER COLUMN password SET NOT NULL;
+ALTER TABLE users DROP COLUMN salt;
+ALTER TABLE users DROP COLUMN hash;
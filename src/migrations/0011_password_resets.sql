@@ -0,0 +1,7 @@
+CREATE TABLE resets (
+code        TEXT PRIMARY KEY,
+userid      INT NOT NULL,
+expires     TIMESTAMP NOT NULL
+);
+
+CREATE INDEX resets_userid_idx ON resets (userid);
@@ -0,0 +1,139 @@
+use postgres::Connection;
+use postgres::error::Error;
+
+use r2d2;
+
+use std::fmt;
+
+// every schema change gets appended here with the next version number; never
+// edit a migration that has already shipped, add a new one instead
+const MIGRATIONS: &[(i32, &str)] = &[
+    (1, include_str!("0001_initial.sql")),
+    (2, include_str!("0002_search.sql")),
+    (3, include_str!("0003_members.sql")),
+    (4, include_str!("0004_room_settings.sql")),
+    (5, include_str!("0005_visibility.sql")),
+    (6, include_str!("0006_direct.sql")),
+    (7, include_str!("0007_notifications.sql")),
+    (8, include_str!("0008_read_markers.sql")),
+    (9, include_str!("0009_sessions.sql")),
+    (10, include_str!("0010_passwords.sql")),
+    (11, include_str!("0011_password_resets.sql"))
+];
+
+// tables that the migrations create, dropped (in this order) by `migrate
+// --reset`
+const TABLES: &[&str] = &[
+    "messages", "users", "tokens", "votes", "history", "privileges", "rooms",
+    "members", "invites", "notifications", "readmarkers", "resets",
+    "schema_version"
+];
+
+pub enum SchemaError {
+    Outdated { current: i32, latest: i32 },
+    TooNew { current: i32, latest: i32 },
+    // couldn't get as far as finding out
+    Database(Error),
+    Pool(r2d2::Error)
+}
+
+impl From<Error> for SchemaError {
+    fn from(e: Error) -> SchemaError {
+        SchemaError::Database(e)
+    }
+}
+
+impl From<r2d2::Error> for SchemaError {
+    fn from(e: r2d2::Error) -> SchemaError {
+        SchemaError::Pool(e)
+    }
+}
+
+impl fmt::Display for SchemaError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            SchemaError::Outdated { current, latest } => write!(f,
+                "database schema is at version {} but this build expects {}; \
+                run `glavra migrate` first", current, latest),
+            SchemaError::TooNew { current, latest } => write!(f,
+                "database schema is at version {} but this build only knows \
+                about {}; refusing to run an older server against it",
+                current, latest),
+            SchemaError::Database(ref e) => write!(f, "database error: {}", e),
+            SchemaError::Pool(ref e) =>
+                write!(f, "couldn't connect to the database: {}", e)
+        }
+    }
+}
+
+pub fn latest_version() -> i32 {
+    MIGRATIONS.last().map(|&(version, _)| version).unwrap_or(0)
+}
+
+pub fn current_version(conn: &Connection) -> Result<i32, Error> {
+    conn.batch_execute("
+        CREATE TABLE IF NOT EXISTS schema_version (
+        version     INT NOT NULL,
+        applied     TIMESTAMP NOT NULL DEFAULT now()
+        );")?;
+
+    let version: Option<i32> = conn.query("
+            SELECT MAX(version) FROM schema_version", &[])?.get(0).get(0);
+    if let Some(version) = version {
+        return Ok(version);
+    }
+
+    // databases created with the old `-r` flag have the initial tables but no
+    // record of it, so adopt them as version 1 instead of recreating them
+    let legacy: bool = conn.query("
+            SELECT EXISTS (
+              SELECT 1 FROM information_schema.tables
+              WHERE table_schema = current_schema()
+                AND table_name = 'messages'
+            )", &[])?.get(0).get(0);
+    if legacy {
+        conn.execute("
+                INSERT INTO schema_version (version) VALUES (1)", &[])?;
+        Ok(1)
+    } else {
+        Ok(0)
+    }
+}
+
+pub fn check(conn: &Connection) -> Result<(), SchemaError> {
+    let (current, latest) = (current_version(conn)?, latest_version());
+    if current < latest {
+        Err(SchemaError::Outdated { current, latest })
+    } else if current > latest {
+        Err(SchemaError::TooNew { current, latest })
+    } else {
+        Ok(())
+    }
+}
+
+// applies every migration newer than the database, each in its own
+// transaction, and returns the version the database ends up at
+pub fn migrate(conn: &Connection) -> Result<i32, Error> {
+    let start = current_version(conn)?;
+    let mut current = start;
+    for &(version, sql) in MIGRATIONS.iter()
+            .filter(|&&(version, _)| version > start) {
+        info!("applying migration {}", version);
+        let trans = conn.transaction()?;
+        trans.batch_execute(sql)?;
+        trans.execute("
+                INSERT INTO schema_version (version) VALUES ($1)",
+                &[&version])?;
+        trans.commit()?;
+        current = version;
+    }
+    Ok(current)
+}
+
+pub fn reset(conn: &Connection) -> Result<(), Error> {
+    for table in TABLES {
+        conn.batch_execute(&format!(
+            "DROP TABLE IF EXISTS {} CASCADE;", table))?;
+    }
+    Ok(())
+}
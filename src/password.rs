@@ -0,0 +1,113 @@
+// passwords are stored as PHC strings, so that each one says how it was
+// hashed and with what parameters:
+//
+//     $argon2id$v=19$m=19456,t=2,p=1$<salt>$<hash>
+//     $bcrypt$c=10$<salt>$<hash>
+//
+// where salts and hashes are unpadded base64. everything new is argon2id;
+// the bcrypt ones are what users had before, and are replaced the next time
+// their owner logs in (as are argon2id ones with outdated parameters)
+
+extern crate argon2;
+extern crate crypto;
+
+use self::argon2::{Variant, Version};
+use self::crypto::bcrypt;
+use self::crypto::util::fixed_time_eq;
+
+use rand::{RngCore, OsRng};
+
+use config::Config;
+
+// hashing takes time proportional to the length of the password, and it
+// all happens before anyone knows whether the password is right
+pub const MAX_LEN: usize = 1024;
+
+const B64: &[u8] =
+    b"ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789+/";
+
+fn argon2_config(config: &Config) -> argon2::Config<'static> {
+    argon2::Config {
+        variant: Variant::Argon2id,
+        version: Version::Version13,
+        mem_cost: config.argon2_memory,
+        time_cost: config.argon2_iterations,
+        lanes: config.argon2_parallelism,
+        ..argon2::Config::default()
+    }
+}
+
+// None if there's no randomness to be had, or the configured parameters are
+// ones argon2 won't take
+pub fn hash(password: &str, config: &Config) -> Option<String> {
+    let mut salt = [0u8; 16];
+    OsRng::new().ok()?.fill_bytes(&mut salt);
+    argon2::hash_encoded(password.as_bytes(), &salt, &argon2_config(config))
+        .map_err(|e| error!("couldn't hash a password: {}", e)).ok()
+}
+
+// anything that isn't a hash we know how to check never matches
+pub fn verify(password: &str, encoded: &str) -> bool {
+    if encoded.starts_with("$argon2id$") {
+        argon2::verify_encoded(encoded, password.as_bytes()).unwrap_or(false)
+    } else if encoded.starts_with("$bcrypt$") {
+        verify_bcrypt(password, encoded)
+    } else {
+        false
+    }
+}
+
+// whether the hash should be replaced by one made with the current settings
+pub fn needs_rehash(encoded: &str, config: &Config) -> bool {
+    !encoded.starts_with(&format!("$argon2id$v=19$m={},t={},p={}$",
+        config.argon2_memory, config.argon2_iterations,
+        config.argon2_parallelism))
+}
+
+fn verify_bcrypt(password: &str, encoded: &str) -> bool {
+    // "", "bcrypt", "c=<cost>", salt, hash
+    let parts: Vec<&str> = encoded.split('$').collect();
+    if parts.len() != 5 || !parts[2].starts_with("c=") {
+        return false;
+    }
+    let cost: u32 = match parts[2][2..].parse() {
+        Ok(cost) => cost,
+        Err(_) => return false
+    };
+    let (salt, hash) = match (b64_decode(parts[3]), b64_decode(parts[4])) {
+        (Some(salt), Some(hash)) => (salt, hash),
+        _ => return false
+    };
+    if salt.len() != 16 || hash.len() != 24 {
+        return false;
+    }
+
+    // the way these were made: only the first 72 bytes counted, and an
+    // empty password was hashed as a single zero byte
+    let password = password.as_bytes();
+    let password = if password.is_empty() { &[0][..] }
+                   else { &password[..password.len().min(72)] };
+    let mut result = [0u8; 24];
+    bcrypt::bcrypt(cost, &salt, password, &mut result);
+    fixed_time_eq(&result, &hash)
+}
+
+fn b64_decode(text: &str) -> Option<Vec<u8>> {
+    let mut bytes = Vec::with_capacity(text.len() * 3 / 4);
+    let mut acc: u32 = 0;
+    let mut bits = 0;
+    for c in text.bytes() {
+        let value = match B64.iter().position(|&b| b == c) {
+            Some(value) => value as u32,
+            None => return None
+        };
+        acc = (acc << 6) | value;
+        bits += 6;
+        if bits >= 8 {
+            bits -= 8;
+            bytes.push((acc >> bits) as u8);
+            acc &= (1 << bits) - 1;
+        }
+    }
+    Some(bytes)
+}
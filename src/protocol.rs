@@ -0,0 +1,474 @@
+// the wire format: every frame in either direction is a JSON object whose
+// "type" field says which variant it is, e.g.
+//
+//     {"type": "message", "text": "hi", "replyid": null}
+//
+// timestamps are seconds since the epoch, and vote types are the integers
+// from types::vote::votetype_to_int
+//
+// a connection can be joined to any number of rooms at once. requests that
+// act on a room take an optional roomid, which has to be one of those, and
+// default to the room given in the connection URL; everything sent about a
+// room says which one in its roomid
+
+use serde_json;
+
+use enums::errcode::ErrCode;
+
+use std::fmt;
+
+// a password or reset code: a plain string on the wire, but kept out of the
+// debug log that every request goes through
+#[derive(Serialize, Deserialize, Clone)]
+pub struct Secret(pub String);
+
+impl fmt::Debug for Secret {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "<redacted>")
+    }
+}
+
+// client -> server
+#[derive(Serialize, Deserialize, Debug, Clone)]
+#[serde(tag = "type", rename_all = "lowercase")]
+pub enum Request {
+    // label is whatever the client wants its session to show up as in the
+    // sessions list, e.g. "firefox on my laptop"
+    Auth {
+        username: String,
+        password: Secret,
+        #[serde(default)]
+        label: Option<String>
+    },
+    Register {
+        username: String,
+        password: Secret,
+        #[serde(default)]
+        label: Option<String>
+    },
+    // every session of the current user that's still valid
+    Sessions,
+    // ends one of the current user's sessions, or without an id the one this
+    // connection is logged in with
+    Logout {
+        #[serde(default)]
+        id: Option<i32>
+    },
+    // ends all of them, this one included
+    LogoutAll,
+    // needs the current password, and logs out every other session
+    ChangePassword {
+        password: Secret,
+        newpassword: Secret
+    },
+    // redeems a code from `glavra resetpassword <username>`, which logs out
+    // every session of that user; doesn't log this connection in
+    ResetPassword {
+        code: Secret,
+        password: Secret
+    },
+    // starts getting everything that happens in a room, after the same
+    // roominfo and replay as connecting with ?room=<id>; invite works like
+    // it does there too
+    Join {
+        roomid: i32,
+        #[serde(default)]
+        invite: Option<String>
+    },
+    // and stops again
+    Leave { roomid: i32 },
+    Message {
+        text: String,
+        #[serde(default)]
+        replyid: Option<i32>,
+        #[serde(default)]
+        roomid: Option<i32>
+    },
+    Edit {
+        id: i32,
+        text: String,
+        #[serde(default)]
+        replyid: Option<i32>
+    },
+    Delete { id: i32 },
+    Vote { messageid: i32, votetype: i32 },
+    // previous revisions of a message
+    History { id: i32 },
+    // a message with everything it replies to (transitively) and its direct
+    // replies
+    Thread { id: i32 },
+    // a page of messages from the current room: the newest ones if no
+    // anchor is given, otherwise those before, after or around (including)
+    // the given message id; at most one anchor may be given
+    Scrollback {
+        #[serde(default)]
+        before: Option<i32>,
+        #[serde(default)]
+        after: Option<i32>,
+        #[serde(default)]
+        around: Option<i32>,
+        #[serde(default)]
+        limit: Option<i64>,
+        #[serde(default)]
+        roomid: Option<i32>
+    },
+    // full-text search over every room the user can read, or just one of
+    // them; since/until are timestamps, hasreply picks only replies (or only
+    // non-replies)
+    Search {
+        query: String,
+        #[serde(default)]
+        roomid: Option<i32>,
+        #[serde(default)]
+        userid: Option<i32>,
+        #[serde(default)]
+        since: Option<i64>,
+        #[serde(default)]
+        until: Option<i64>,
+        #[serde(default)]
+        hasreply: Option<bool>,
+        #[serde(default)]
+        limit: Option<i64>
+    },
+    // moves messages from the current room into another one (`to`, which
+    // doesn't have to be joined); needs MoveOut here and MoveIn there
+    Move {
+        ids: Vec<i32>,
+        to: i32,
+        #[serde(default)]
+        roomid: Option<i32>
+    },
+    // moves the current user's read marker in the current room up to the
+    // given message, or the newest one
+    MarkRead {
+        #[serde(default)]
+        messageid: Option<i32>,
+        #[serde(default)]
+        roomid: Option<i32>
+    },
+    // who is in the current room right now
+    Who {
+        #[serde(default)]
+        roomid: Option<i32>
+    },
+    // unread mentions of and replies to the current user
+    Inbox,
+    // marks notifications as read: the given ones, or all of them
+    ReadInbox {
+        #[serde(default)]
+        ids: Option<Vec<i32>>
+    },
+    // creates a new room
+    Room { name: String, desc: String },
+    // changes any of the current room's name, description and topic
+    EditRoom {
+        #[serde(default)]
+        name: Option<String>,
+        #[serde(default)]
+        desc: Option<String>,
+        #[serde(default)]
+        topic: Option<String>,
+        #[serde(default)]
+        roomid: Option<i32>
+    },
+    // makes the current room read-only, or writable again
+    ArchiveRoom {
+        #[serde(default)]
+        roomid: Option<i32>
+    },
+    UnarchiveRoom {
+        #[serde(default)]
+        roomid: Option<i32>
+    },
+    // public, unlisted or invite (see enums::visibility)
+    SetVisibility {
+        visibility: String,
+        #[serde(default)]
+        roomid: Option<i32>
+    },
+    // a token that lets whoever has it join the current room as a member,
+    // by connecting with ?room=<id>&invite=<token>
+    CreateInvite {
+        #[serde(default)]
+        roomid: Option<i32>
+    },
+    // opens (or finds) the direct message conversation between the current
+    // user and these users, which is then joined like any other room
+    Dm { userids: Vec<i32> },
+    // every conversation the current user is part of
+    Dms,
+    // the privilege management requests all act on the current room and
+    // need ModifyPrivs there; privtype is a name as in PrivType::from_name
+    //
+    // lists every privilege row of the room
+    GetPrivs {
+        #[serde(default)]
+        roomid: Option<i32>
+    },
+    // sets a privilege row, for one user or (without a userid) for everyone
+    SetPriv {
+        privtype: String,
+        #[serde(default)]
+        userid: Option<i32>,
+        threshold: i32,
+        period: i32,
+        #[serde(default)]
+        roomid: Option<i32>
+    },
+    // removes a row, so that the room-wide one (or the configured default)
+    // applies again
+    ClearPriv {
+        privtype: String,
+        #[serde(default)]
+        userid: Option<i32>,
+        #[serde(default)]
+        roomid: Option<i32>
+    },
+    // everyone with a role in the current room
+    Members {
+        #[serde(default)]
+        roomid: Option<i32>
+    },
+    // gives a user a role in the current room (see enums::role); needs
+    // ModifyPrivs, and only owners can touch owners or make new ones
+    SetRole {
+        userid: i32,
+        role: String,
+        #[serde(default)]
+        roomid: Option<i32>
+    }
+}
+
+// server -> client
+#[derive(Serialize, Deserialize, Debug, Clone)]
+#[serde(tag = "type", rename_all = "lowercase")]
+pub enum Response {
+    // token and userid are only sent in reply to an auth request; username
+    // only when logging in with a token in the connection URL
+    Auth {
+        success: bool,
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        token: Option<String>,
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        userid: Option<i32>,
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        username: Option<String>
+    },
+    Register {
+        success: bool,
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        token: Option<String>,
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        userid: Option<i32>
+    },
+    Preferences { theme: String },
+    // in reply to sessions, newest first
+    Sessions { sessions: Vec<SessionFrame> },
+    // sent to every connection of a session that just ended, right before
+    // the server closes it
+    Logout { id: i32 },
+    // success is false for a wrong current password or reset code
+    ChangePassword { success: bool },
+    ResetPassword { success: bool },
+    // also broadcast to the room whenever any of it changes
+    RoomInfo {
+        roomid: i32,
+        name: String,
+        desc: String,
+        topic: String,
+        archived: bool,
+        visibility: String,
+        direct: bool
+    },
+    // one of these per room, in reply to ?queryrooms
+    RoomList {
+        id: i32,
+        name: String,
+        desc: String,
+        topic: String,
+        archived: bool,
+        visibility: String,
+        // only for logged in users
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        unread: Option<i64>
+    },
+    // one of these per user, in reply to ?queryusers
+    UserList { id: i32, username: String },
+    UserInfo { id: i32, username: String },
+    Message(MessageFrame),
+    Edit(MessageFrame),
+    Vote(VoteFrame),
+    UndoVote(VoteFrame),
+    Starboard { roomid: i32, votetype: i32, messages: Vec<StarredFrame> },
+    History { roomid: i32, revisions: Vec<RevisionFrame> },
+    // oldest first
+    Scrollback { roomid: i32, messages: Vec<ScrollbackFrame> },
+    // ancestors start from the root of the thread; replies are oldest first
+    Thread {
+        roomid: i32,
+        ancestors: Vec<ScrollbackFrame>,
+        message: ScrollbackFrame,
+        replies: Vec<ScrollbackFrame>
+    },
+    // best match first
+    Search { results: Vec<SearchFrame> },
+    Room { success: bool, id: i32 },
+    // in reply to leave, and also sent when a ban takes the connection out of
+    // a room
+    Leave { roomid: i32 },
+    // broadcast when a user opens their first connection to a room, or
+    // closes their last one
+    Presence { roomid: i32, userid: i32, username: String, online: bool },
+    // in reply to who, by user id
+    Who { roomid: i32, users: Vec<UserFrame> },
+    // everything after messageid is new to the user; part of the replay when
+    // joining a room, and sent to all of a user's sockets on markread
+    ReadMarker { roomid: i32, messageid: i32 },
+    // sent to every socket of the user concerned, whatever room it's in
+    Notify(NotificationFrame),
+    // in reply to inbox, newest first
+    Inbox { notifications: Vec<NotificationFrame> },
+    // broadcast to the room messages were moved out of, with where they went
+    MoveOut { roomid: i32, ids: Vec<i32>, to: i32 },
+    // and to the room they were moved into, with where they came from
+    MoveIn { roomid: i32, messages: Vec<ScrollbackFrame>, from: i32 },
+    Invite { roomid: i32, token: String },
+    Dm(DmFrame),
+    // in reply to dms, newest first
+    DmList { dms: Vec<DmFrame> },
+    // in reply to getprivs
+    Privileges { roomid: i32, privileges: Vec<PrivilegeFrame> },
+    // broadcast to the room whenever a row is set or cleared
+    Privilege(PrivilegeFrame),
+    ClearPriv { roomid: i32, privtype: String, userid: Option<i32> },
+    // in reply to members
+    Members { roomid: i32, members: Vec<MemberFrame> },
+    // broadcast to the room when someone's role changes
+    Role { roomid: i32, userid: i32, role: String },
+    Error {
+        code: ErrCode,
+        // a human-readable explanation, when there's more to say than the code
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        reason: Option<String>,
+        // for the throttling errors, how many seconds until it's worth trying
+        // again
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        retryafter: Option<i64>
+    }
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct MessageFrame {
+    pub id: i32,
+    pub roomid: i32,
+    pub userid: i32,
+    pub replyid: Option<i32>,
+    pub username: String,
+    pub text: String,
+    pub timestamp: i64,
+    // how many (undeleted) messages reply to this one
+    pub replycount: i64
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct VoteFrame {
+    pub roomid: i32,
+    pub messageid: i32,
+    pub userid: i32,
+    pub votetype: i32
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct ScrollbackFrame {
+    #[serde(flatten)]
+    pub message: MessageFrame,
+    pub votes: Vec<VoteFrame>
+}
+
+// snippet is the message text as HTML, with &, < and > escaped and the
+// matching words wrapped in <mark></mark>
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct SearchFrame {
+    pub id: i32,
+    pub roomid: i32,
+    pub userid: i32,
+    pub replyid: Option<i32>,
+    pub username: String,
+    pub snippet: String,
+    pub timestamp: i64,
+    pub rank: f32
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct StarredFrame {
+    pub id: i32,
+    pub text: String,
+    pub timestamp: i64,
+    pub userid: i32,
+    pub username: String,
+    pub votecount: i64
+}
+
+// kind is "mention" or "reply"
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct NotificationFrame {
+    pub id: i32,
+    pub kind: String,
+    pub roomid: i32,
+    pub message: MessageFrame,
+    pub timestamp: i64
+}
+
+// users includes the current user
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct DmFrame {
+    pub roomid: i32,
+    pub users: Vec<UserFrame>
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct UserFrame {
+    pub id: i32,
+    pub username: String
+}
+
+// current is whether it's the session of the connection asking
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct SessionFrame {
+    pub id: i32,
+    pub label: String,
+    pub created: i64,
+    pub lastused: i64,
+    pub expires: i64,
+    pub current: bool
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct MemberFrame {
+    pub userid: i32,
+    pub username: String,
+    pub role: String
+}
+
+// period is in seconds; a userid of null means the row is room-wide
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct PrivilegeFrame {
+    pub roomid: i32,
+    pub privtype: String,
+    pub userid: Option<i32>,
+    pub threshold: i32,
+    pub period: i32
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct RevisionFrame {
+    pub replyid: Option<i32>,
+    pub text: String,
+    pub timestamp: i64
+}
+
+impl Response {
+    pub fn to_json(&self) -> String {
+        serde_json::to_string(self).unwrap()
+    }
+}
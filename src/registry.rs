@@ -0,0 +1,133 @@
+use ws;
+use ws::util::Token;
+
+use std::collections::{HashMap, HashSet};
+use std::sync::Mutex;
+
+// keeps track of which sockets are currently joined to which room (or logged
+// in as which user), so that events only get sent to the people who are
+// actually in that room
+pub struct Registry {
+    rooms: Mutex<HashMap<i32, HashMap<Token, ws::Sender>>>
+}
+
+impl Registry {
+
+    pub fn new() -> Registry {
+        Registry {
+            rooms: Mutex::new(HashMap::new())
+        }
+    }
+
+    pub fn join(&self, roomid: i32, out: &ws::Sender) {
+        self.rooms.lock().unwrap().entry(roomid).or_default()
+            .insert(out.token(), out.clone());
+    }
+
+    pub fn leave(&self, roomid: i32, out: &ws::Sender) {
+        let mut rooms = self.rooms.lock().unwrap();
+        let empty = match rooms.get_mut(&roomid) {
+            Some(members) => {
+                members.remove(&out.token());
+                members.is_empty()
+            },
+            None => false
+        };
+        if empty {
+            rooms.remove(&roomid);
+        }
+    }
+
+    // one socket that can't be sent to doesn't keep the rest from hearing
+    // about it; it's on its way out anyway, and leaves once it's closed
+    pub fn broadcast(&self, roomid: i32, msg: String) {
+        if let Some(members) = self.rooms.lock().unwrap().get(&roomid) {
+            for out in members.values() {
+                if let Err(e) = out.send(msg.clone()) {
+                    warn!("couldn't send to {:?}: {}", out.token(), e);
+                }
+            }
+        }
+    }
+
+    // has every socket's on_timeout called with the token right away
+    pub fn wake(&self, roomid: i32, token: Token) {
+        if let Some(members) = self.rooms.lock().unwrap().get(&roomid) {
+            for out in members.values() {
+                if let Err(e) = out.timeout(0, token) {
+                    warn!("couldn't wake {:?}: {}", out.token(), e);
+                }
+            }
+        }
+    }
+
+    // the sockets take themselves out of the registry once they're closed
+    pub fn close(&self, roomid: i32) {
+        if let Some(members) = self.rooms.lock().unwrap().get(&roomid) {
+            for out in members.values() {
+                if let Err(e) = out.close(ws::CloseCode::Normal) {
+                    warn!("couldn't close {:?}: {}", out.token(), e);
+                }
+            }
+        }
+    }
+
+}
+
+// who is in which room right now, counting each user once however many
+// sockets they have open there; nothing here is ever persisted
+pub struct Presence {
+    rooms: Mutex<HashMap<i32, HashMap<i32, HashSet<Token>>>>
+}
+
+impl Presence {
+
+    pub fn new() -> Presence {
+        Presence {
+            rooms: Mutex::new(HashMap::new())
+        }
+    }
+
+    // returns whether this is the user's first connection to the room
+    pub fn arrive(&self, roomid: i32, userid: i32, out: &ws::Sender) -> bool {
+        let mut rooms = self.rooms.lock().unwrap();
+        let connections = rooms.entry(roomid).or_default()
+            .entry(userid).or_default();
+        connections.insert(out.token());
+        connections.len() == 1
+    }
+
+    // returns whether that was the user's last connection to the room
+    pub fn depart(&self, roomid: i32, userid: i32, out: &ws::Sender) -> bool {
+        let mut rooms = self.rooms.lock().unwrap();
+        let (gone, empty) = match rooms.get_mut(&roomid) {
+            Some(users) => {
+                let gone = match users.get_mut(&userid) {
+                    Some(connections) => {
+                        connections.remove(&out.token());
+                        connections.is_empty()
+                    },
+                    None => false
+                };
+                if gone {
+                    users.remove(&userid);
+                }
+                (gone, users.is_empty())
+            },
+            None => (false, false)
+        };
+        if empty {
+            rooms.remove(&roomid);
+        }
+        gone
+    }
+
+    // the users in the room, by id
+    pub fn who(&self, roomid: i32) -> Vec<i32> {
+        let mut users: Vec<i32> = self.rooms.lock().unwrap().get(&roomid)
+            .map_or(Vec::new(), |users| users.keys().cloned().collect());
+        users.sort();
+        users
+    }
+
+}
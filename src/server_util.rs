@@ -1,128 +1,251 @@
 use ws;
 
-use serde_json;
-use serde_json::Value;
-
-use rand::{Rng, OsRng};
-use rand::distributions::Alphanumeric;
-
-use postgres;
-
 use time;
-use time::Timespec;
-
-use std::sync::MutexGuard;
+use time::{Duration, Timespec};
 
 use types::message::*;
 use types::vote::*;
+use types::privilege::*;
+use types::room::*;
+use types::notification::*;
+use types::session::*;
 use enums::errcode::*;
 use enums::privtype::*;
+use enums::role::*;
+use enums::visibility::*;
+use protocol::*;
 
-use Glavra;
+use util;
 use Server;
+use RECHECK;
+
+use std::cmp;
 
 impl Server {
 
-    pub fn send_message(&self, message: Message, lock: &MutexGuard<Glavra>) {
+    pub fn send(&self, response: Response) -> ws::Result<()> {
+        self.out.send(response.to_json())
+    }
+
+    // switches the connection over to a user and session, which may not be
+    // the ones it was logged in with before
+    pub fn log_in(&mut self, userid: i32, session: i32) {
+        if let Some(previous) = self.userid {
+            self.glavra.users.leave(previous, &self.out);
+            for &roomid in &self.joined {
+                self.depart(roomid);
+            }
+        }
+        if let Some(previous) = self.session {
+            self.glavra.sessions.leave(previous, &self.out);
+        }
+        self.userid = Some(userid);
+        self.session = Some(session);
+        self.glavra.users.join(userid, &self.out);
+        self.glavra.sessions.join(session, &self.out);
+        for &roomid in &self.joined {
+            self.arrive(roomid);
+        }
+    }
+
+    // the current user is now here, in a room this connection has joined;
+    // the room only hears about it when it's their first connection there
+    pub fn arrive(&self, roomid: i32) {
+        if let Some(userid) = self.userid {
+            if self.glavra.presence.arrive(roomid, userid, &self.out) {
+                self.glavra.rooms.broadcast(roomid,
+                    self.presence_frame(roomid, userid, true).to_json());
+            }
+        }
+    }
+
+    // and the opposite, once their last connection is gone
+    pub fn depart(&self, roomid: i32) {
+        if let Some(userid) = self.userid {
+            if self.glavra.presence.depart(roomid, userid, &self.out) {
+                self.glavra.rooms.broadcast(roomid,
+                    self.presence_frame(roomid, userid, false).to_json());
+            }
+        }
+    }
+
+    pub fn presence_frame(&self, roomid: i32, userid: i32, online: bool)
+            -> Response {
+        Response::Presence {
+            roomid,
+            userid,
+            username: self.get_username(userid).unwrap_or_default(),
+            online
+        }
+    }
+
+    pub fn send_message(&self, message: Message) {
         let mut message = message;
         let edit;
         if message.id == -1 {
             edit = false;
-            message.id = lock.conn.query("
-                    INSERT INTO messages (roomid, userid, replyid, text, tstamp)
-                    VALUES ($1, $2, $3, $4, $5)
-                    RETURNING id",
-                    &[&self.roomid.unwrap(), &message.userid, &message.replyid,
-                        &message.text, &message.timestamp])
-                .unwrap().get(0).get(0);
+            message.id = self.glavra.store.insert_message(&message);
         } else {
             edit = true;
-            let oldquery = lock.conn.query("
-                    SELECT replyid, text FROM messages WHERE id = $1",
-                    &[&message.id]).unwrap();
-            let (oldreplyid, oldtext) =
-                (oldquery.get(0).get::<usize, Option<i32>>(0),
-                 oldquery.get(0).get::<usize, String>(1));
-            if oldtext.is_empty() {
+            let old = self.glavra.store.get_message(message.id).unwrap();
+            if old.text.is_empty() {
                 self.send_error(ErrCode::EditDeleted);
-            } else {
-                lock.conn.execute("INSERT INTO history
-                        (messageid, replyid, text, tstamp)
-                        VALUES ($1, $2, $3, $4)",
-                        &[&message.id, &oldreplyid, &oldtext, &time::get_time()])
-                    .unwrap();
-                lock.conn.execute("UPDATE messages
-                        SET replyid = $1, text = $2 WHERE id = $3",
-                        &[&message.replyid, &message.text, &message.id])
-                    .unwrap();
+                return;
             }
+            self.glavra.store.edit_message(message.id, message.replyid,
+                &message.text, time::get_time());
         }
-        self.out.broadcast(self.message_json(&message, edit, &lock)).unwrap();
+        let frame = self.message_frame(&message);
+        let response = if edit { Response::Edit(frame) }
+                       else { Response::Message(frame) };
+        self.glavra.rooms.broadcast(message.roomid, response.to_json());
+        self.notify(&message);
     }
 
-    pub fn message_json(&self, message: &Message, edit: bool,
-            lock: &MutexGuard<Glavra>) -> String {
-        serde_json::to_string(&json!({
-            "type": if edit { "edit" } else { "message" },
-            "id": message.id,
-            "userid": message.userid,
-            "replyid": message.replyid,
-            "username": self.get_username(message.userid, lock).unwrap(),
-            "text": &message.text,
-            "timestamp": message.timestamp.sec
-        })).unwrap()
+    // tells everyone mentioned in the message, or whose message it replies
+    // to, about it; edits only notify people who weren't already notified
+    fn notify(&self, message: &Message) {
+        if message.userid == -1 || message.text.is_empty() { return; }
+        let store = &self.glavra.store;
+        let room = match store.get_room(message.roomid) {
+            Some(room) => room,
+            None => return
+        };
+
+        let mut recipients: Vec<(i32, NotifyKind)> = util::mentions(
+                &message.text).into_iter()
+            .filter_map(|username| store.find_user(&username))
+            .map(|user| (user.id, NotifyKind::Mention)).collect();
+        if let Some(parent) = message.replyid
+                .and_then(|replyid| store.get_message(replyid)) {
+            recipients.push((parent.userid, NotifyKind::Reply));
+        }
+
+        for (userid, kind) in recipients {
+            if userid == message.userid || userid == -1 ||
+                    !self.user_can_read(&room, userid) {
+                continue;
+            }
+            if let Some(id) = store.add_notification(userid, message.id, kind,
+                    message.timestamp) {
+                let frame = self.notification_frame(&Notification {
+                    id,
+                    userid,
+                    messageid: message.id,
+                    kind,
+                    timestamp: message.timestamp,
+                    read: false
+                }, message);
+                self.glavra.users.broadcast(userid,
+                    Response::Notify(frame).to_json());
+            }
+        }
     }
 
-    pub fn system_message(&self, text: String, lock: &MutexGuard<Glavra>) {
+    pub fn notification_frame(&self, notification: &Notification,
+                              message: &Message) -> NotificationFrame {
+        NotificationFrame {
+            id: notification.id,
+            kind: notifykind_name(notification.kind).to_string(),
+            roomid: message.roomid,
+            message: self.message_frame(message),
+            timestamp: notification.timestamp.sec
+        }
+    }
+
+    pub fn message_frame(&self, message: &Message) -> MessageFrame {
+        MessageFrame {
+            id: message.id,
+            roomid: message.roomid,
+            userid: message.userid,
+            replyid: message.replyid,
+            username: self.get_username(message.userid).unwrap(),
+            text: message.text.clone(),
+            timestamp: message.timestamp.sec,
+            replycount: self.glavra.store.count_replies(message.id,
+                message.roomid)
+        }
+    }
+
+    // a reply has to point at an undeleted message in the same room that's
+    // older than the reply itself (`id`, or a new message if None), which
+    // also keeps reply chains from ever going in circles
+    pub fn valid_reply(&self, roomid: i32, replyid: Option<i32>,
+                       id: Option<i32>) -> bool {
+        let replyid = match replyid {
+            Some(replyid) => replyid,
+            None => return true
+        };
+        if id.is_some_and(|id| replyid >= id) { return false; }
+        self.glavra.store.get_message(replyid).is_some_and(|parent|
+            parent.roomid == roomid && !parent.text.is_empty())
+    }
+
+    // a message along with every vote on it
+    pub fn scrollback_frame(&self, message: &Message) -> ScrollbackFrame {
+        ScrollbackFrame {
+            message: self.message_frame(message),
+            votes: self.glavra.store.message_votes(message.id).iter()
+                .map(|vote| self.vote_frame(vote, message.roomid)).collect()
+        }
+    }
+
+    pub fn system_message(&self, roomid: i32, text: String) {
+        if self.is_archived(roomid) { return; }
         let message = Message {
             id: -1,
-            roomid: self.roomid.unwrap(),
+            roomid,
             userid: -1,
             replyid: None,
-            text: text,
+            text,
             timestamp: time::get_time()
         };
-        self.send_message(message, &lock);
+        self.send_message(message);
     }
 
-    pub fn send_vote(&self, vote: Vote, lock: &MutexGuard<Glavra>) {
-        let voteid = lock.conn
-            .query("SELECT id FROM votes
-                    WHERE messageid = $1 AND userid = $2 AND votetype = $3",
-                    &[&vote.messageid, &vote.userid,
-                        &votetype_to_int(&vote.votetype)])
-            .unwrap();
+    pub fn send_vote(&self, vote: Vote, roomid: i32) {
         let undo;
-        if voteid.is_empty() {
-            undo = false;
-            lock.conn.execute("INSERT INTO votes
-                    (messageid, userid, votetype, tstamp)
-                    VALUES ($1, $2, $3, $4)",
-                    &[&vote.messageid, &vote.userid,
-                    &votetype_to_int(&vote.votetype), &vote.timestamp])
-                .unwrap();
-        } else {
-            undo = true;
-            lock.conn.execute("DELETE FROM votes WHERE id = $1",
-                &[&voteid.get(0).get::<usize, i32>(0)]).unwrap();
+        match self.glavra.store.find_vote(vote.messageid, vote.userid,
+                &vote.votetype) {
+            None => {
+                undo = false;
+                self.glavra.store.insert_vote(&vote);
+            },
+            Some(voteid) => {
+                undo = true;
+                self.glavra.store.delete_vote(voteid);
+            }
         }
-        self.out.broadcast(self.vote_json(&vote, undo)).unwrap();
+        let frame = self.vote_frame(&vote, roomid);
+        let response = if undo { Response::UndoVote(frame) }
+                       else { Response::Vote(frame) };
+        self.glavra.rooms.broadcast(roomid, response.to_json());
     }
 
-    pub fn vote_json(&self, vote: &Vote, undo: bool) -> String {
-        serde_json::to_string(&json!({
-            "type": if undo { "undovote" } else { "vote" },
-            "messageid": vote.messageid,
-            "userid": vote.userid,
-            "votetype": votetype_to_int(&vote.votetype)
-        })).unwrap()
+    // roomid is the room of the message voted on
+    pub fn vote_frame(&self, vote: &Vote, roomid: i32) -> VoteFrame {
+        VoteFrame {
+            roomid,
+            messageid: vote.messageid,
+            userid: vote.userid,
+            votetype: votetype_to_int(&vote.votetype)
+        }
     }
 
     pub fn send_error(&self, err: ErrCode) {
-        self.out.send(serde_json::to_string(&json!({
-            "type": "error",
-            "code": err as i32
-        })).unwrap()).unwrap();
+        self.send(Response::Error {
+            code: err,
+            reason: None,
+            retryafter: None
+        }).unwrap();
+    }
+
+    pub fn send_retry(&self, err: ErrCode, seconds: i64) {
+        self.send(Response::Error {
+            code: err,
+            reason: None,
+            retryafter: Some(seconds)
+        }).unwrap();
     }
 
     pub fn error_close(&self, err: ErrCode) {
@@ -130,113 +253,304 @@ impl Server {
         self.out.close(ws::CloseCode::Unsupported).unwrap();
     }
 
-    pub fn get_username(&self, userid: i32, lock: &MutexGuard<Glavra>)
-            -> Result<String, postgres::error::Error> {
-        if userid == -1 { return Ok(String::new()); }
-        lock.conn.query("SELECT username FROM users
-            WHERE id = $1", &[&userid]).map(|rows|
-                rows.get(0).get::<usize, String>(0))
+    pub fn get_username(&self, userid: i32) -> Option<String> {
+        if userid == -1 { return Some(String::new()); }
+        self.glavra.store.get_user(userid).map(|user| user.username)
+    }
+
+    // returns the threshold for the privilege, along with the start of the
+    // period it applies to (i.e. "at most threshold times since then")
+    //
+    // the first of these that exists applies:
+    //
+    //   - a ban, which denies everything
+    //   - a privilege row for this specific user
+    //   - whatever the user's role in the room implies
+    //   - the room-wide privilege row
+    //   - the configured default for new rooms
+    //
+    // and a privilege that isn't configured either is denied outright
+    pub fn get_privilege(&self, roomid: i32, userid: &Option<i32>,
+                         privtype: PrivType) -> (i64, Timespec) {
+        let store = &self.glavra.store;
+        let role = userid.and_then(|userid| store.get_role(roomid, userid));
+        let (threshold, period) = if role == Some(Role::Banned) {
+            (0, 0.0)
+        } else {
+            userid.and_then(|userid|
+                    store.get_privilege(roomid, Some(userid), privtype))
+                .or_else(|| role.and_then(|role| role.privilege(privtype)))
+                .or_else(|| store.get_privilege(roomid, None, privtype))
+                .or_else(|| self.glavra.config.ratelimits.iter()
+                    .find(|&&(p, _)| p == privtype)
+                    .map(|&(_, limit)| (limit.threshold as i64,
+                        limit.period as f64)))
+                .unwrap_or((0, 0.0))
+        };
+        (threshold, time::get_time() -
+            Duration::milliseconds((period * 1000.0) as i64))
     }
 
-    pub fn get_sender(&self, messageid: i32, lock: &MutexGuard<Glavra>)
-            -> Result<i32, postgres::error::Error> {
-        lock.conn.query("SELECT userid FROM messages
-            WHERE id = $1", &[&messageid]).map(|rows|
-                rows.get(0).get::<usize, i32>(0))
+    // for the privileges that are simply granted or not
+    pub fn has_privilege(&self, roomid: i32, privtype: PrivType) -> bool {
+        self.get_privilege(roomid, &self.userid, privtype).0 > 0
     }
 
-    pub fn get_privilege(&self, roomid: i32, userid: &Option<i32>,
-                         privtype: PrivType, lock: &MutexGuard<Glavra>)
-            -> Result<(i64, f64), postgres::error::Error> {
-        let privtype = privtype as i32;
-        lock.conn.query("
-                SELECT threshold, EXTRACT(EPOCH FROM period)::REAL
-                FROM privileges
-                WHERE roomid = $1
-                  AND (userid = $2 OR userid IS NULL)
-                  AND privtype = $3
-                ORDER BY userid", &[&roomid, userid, &privtype])
-            .map(|rows| {
-                 let row = rows.get(0);
-                 (row.get::<usize, i32>(0) as i64,
-                  row.get::<usize, f32>(1) as f64)
-            })
-    }
-
-    pub fn starboard_json(&self, votetype: VoteType, lock: &MutexGuard<Glavra>)
-            -> String {
-        serde_json::to_string(&json!({
-            "type": "starboard",
-            "votetype": votetype_to_int(&votetype),
-            "messages": lock.conn.query(match votetype {
-                VoteType::Star =>
-                    "SELECT m.id, m.text, m.tstamp,
-                           MIN(u.id), MIN(u.username),
-                           COUNT(v.userid)
-                    FROM votes v
-                      INNER JOIN messages m ON v.messageid = m.id
-                      LEFT JOIN users u ON m.userid = u.id
-                    WHERE v.votetype = 3 AND m.roomid = $1
-                    GROUP BY m.id
-                    ORDER BY (COUNT(v.userid) * POW(
-                      EXTRACT(EPOCH FROM (NOW() - m.tstamp)) / 60,
-                      -1.5)) DESC
-                    LIMIT 10",
-                VoteType::Pin =>
-                    "SELECT m.id, m.text, m.tstamp,
-                           MIN(u.id), MIN(u.username),
-                           COUNT(v.userid)
-                    FROM votes v
-                      INNER JOIN messages m ON v.messageid = m.id
-                      LEFT JOIN users u ON m.userid = u.id
-                    WHERE v.votetype = 4 AND m.roomid = $1
-                    GROUP BY m.id
-                    ORDER BY COUNT(v.userid)",
-                _ => panic!("weird votetype in starboard_json")
-            }, &[&self.roomid.unwrap()]).unwrap().iter().map(|row|
-                json!({
-                    "id": row.get::<usize, i32>(0),
-                    "text": row.get::<usize, String>(1),
-                    "timestamp": row.get::<usize, Timespec>(2).sec,
-                    "userid": row.get::<usize, Option<i32>>(3).unwrap_or(-1),
-                    "username": row.get::<usize, Option<String>>(4).unwrap_or_else(|| String::new()),
-                    "votecount": row.get::<usize, i64>(5)
-                })
-            ).collect::<Vec<Value>>()
-        })).unwrap()
-    }
-
-    pub fn history_json(&self, id: i32, lock: &MutexGuard<Glavra>) -> String {
-        serde_json::to_string(&json!({
-            "type": "history",
-            "revisions": lock.conn.query("
-                SELECT replyid, text, tstamp
-                FROM history
-                WHERE messageid = $1
-                ORDER BY id", &[&id]).unwrap().iter().map(|row| json!({
-                    "replyid": row.get::<usize, Option<i32>>(0),
-                    "text": row.get::<usize, String>(1),
-                    "timestamp": row.get::<usize, Timespec>(2).sec
-                })).collect::<Vec<Value>>()
-        })).unwrap()
-    }
-
-    pub fn get_auth_token(&self, userid: i32, lock: &MutexGuard<Glavra>)
-            -> String {
-        let token_query = lock.conn.query("
-                SELECT token
-                FROM tokens
-                WHERE userid = $1", &[&userid]).unwrap();
-        if !token_query.is_empty() {
-            return token_query.get(0).get(0);
-        }
-
-        let mut rng = OsRng::new().unwrap();
-        let token: String = rng.sample_iter(&Alphanumeric).take(32).collect();
-        lock.conn.execute("
-                INSERT INTO tokens (userid, token)
-                VALUES ($1, $2)", &[&userid, &token])
-            .unwrap();
-        token
+    pub fn roominfo_frame(&self, room: Room) -> Response {
+        Response::RoomInfo {
+            roomid: room.id,
+            name: room.name,
+            desc: room.description,
+            topic: room.topic,
+            archived: room.archived,
+            visibility: room.visibility.name().to_string(),
+            direct: room.direct
+        }
+    }
+
+    // whether the current user gets to change the privileges of the given
+    // one: owners are only up to other owners, the same as with roles
+    pub fn can_modify(&self, roomid: i32, userid: i32) -> bool {
+        let store = &self.glavra.store;
+        store.get_role(roomid, userid) != Some(Role::Owner) ||
+            self.userid.and_then(|me| store.get_role(roomid, me)) ==
+                Some(Role::Owner)
+    }
+
+    // a ReadAccess row for the user (or the whole room, if None) changed, so
+    // whoever just lost it has to go
+    pub fn recheck_access(&self, roomid: i32, userid: Option<i32>) {
+        match userid {
+            Some(userid) => self.glavra.users.wake(userid, RECHECK),
+            None => self.glavra.rooms.wake(roomid, RECHECK)
+        }
+    }
+
+    // anyone with a role in the room other than banned
+    pub fn is_member(&self, roomid: i32) -> bool {
+        match self.userid.and_then(|userid|
+                self.glavra.store.get_role(roomid, userid)) {
+            Some(Role::Banned) | None => false,
+            Some(_) => true
+        }
+    }
+
+    // like can_join plus ReadAccess, but for any user rather than the
+    // current one
+    pub fn user_can_read(&self, room: &Room, userid: i32) -> bool {
+        let member = match self.glavra.store.get_role(room.id, userid) {
+            Some(Role::Banned) | None => false,
+            Some(_) => true
+        };
+        (room.visibility != Visibility::Invite || member) &&
+            self.get_privilege(room.id, &Some(userid),
+                PrivType::ReadAccess).0 > 0
+    }
+
+    // whether the room shows up in queryrooms for the current user; direct
+    // message conversations never do, they're listed by the dms request
+    pub fn can_see(&self, room: &Room) -> bool {
+        !room.direct &&
+            (room.visibility == Visibility::Public || self.is_member(room.id))
+    }
+
+    pub fn dm_frame(&self, roomid: i32) -> DmFrame {
+        DmFrame {
+            roomid,
+            users: self.glavra.store.list_members(roomid).into_iter()
+                .filter_map(|(userid, _)| self.get_username(userid)
+                    .map(|username| UserFrame {
+                        id: userid,
+                        username
+                    })).collect()
+        }
+    }
+
+    // whether the current user can open the room (ReadAccess aside)
+    pub fn can_join(&self, room: &Room) -> bool {
+        room.visibility != Visibility::Invite || self.is_member(room.id)
+    }
+
+    // everything that has to hold for the current user to join a room, for
+    // both ?room=<id> and the join request; a valid invite makes a logged in
+    // user a member for good, so the link is only needed the first time
+    pub fn check_join(&self, roomid: i32, invite: Option<String>)
+            -> Result<Room, ErrCode> {
+        let room = self.glavra.store.get_room(roomid)
+            .ok_or(ErrCode::RoomNotExist)?;
+
+        if let (Some(token), Some(userid)) = (invite, self.userid) {
+            if self.glavra.store.invite_room(&token) == Some(roomid) &&
+                    self.glavra.store.get_role(roomid, userid).is_none() {
+                self.glavra.store.set_role(roomid, userid, Role::Member);
+            }
+        }
+
+        if !self.can_join(&room) {
+            return Err(ErrCode::NotMember);
+        }
+        if !self.has_privilege(roomid, PrivType::ReadAccess) {
+            return Err(ErrCode::NoPrivilege);
+        }
+        Ok(room)
+    }
+
+    // starts sending the connection everything that happens in the room,
+    // after catching it up with the room info and the replay
+    pub fn join_room(&mut self, room: Room) -> ws::Result<()> {
+        let roomid = room.id;
+        self.joined.insert(roomid);
+        self.glavra.rooms.join(roomid, &self.out);
+
+        self.send(self.roominfo_frame(room))?;
+
+        // the read marker goes right before the first message the user
+        // hasn't seen yet, if any of the replay is new to them
+        let mut marker = self.userid.and_then(|userid|
+            self.glavra.store.get_read_marker(userid, roomid));
+        for message in self.glavra.store.recent_messages(roomid,
+                self.glavra.config.history_size) {
+            if let Some(messageid) = marker {
+                if message.id > messageid {
+                    self.send(Response::ReadMarker {
+                        roomid,
+                        messageid
+                    })?;
+                    marker = None;
+                }
+            }
+            self.send(Response::Message(self.message_frame(&message)))?;
+            for vote in self.glavra.store.message_votes(message.id) {
+                let frame = self.vote_frame(&vote, roomid);
+                self.send(Response::Vote(frame))?;
+            }
+        }
+
+        self.send(self.starboard_frame(roomid, VoteType::Star))?;
+        self.send(self.starboard_frame(roomid, VoteType::Pin))?;
+
+        self.arrive(roomid);
+        Ok(())
+    }
+
+    // stops sending the connection anything about the room
+    pub fn leave_room(&mut self, roomid: i32) {
+        self.joined.remove(&roomid);
+        if self.roomid == Some(roomid) {
+            self.roomid = None;
+        }
+        self.glavra.rooms.leave(roomid, &self.out);
+        self.depart(roomid)
+    }
+
+    // whether the connection gets events from the room
+    pub fn is_joined(&self, roomid: i32) -> bool {
+        self.joined.contains(&roomid)
+    }
+
+    // nothing in an archived room can change until it's unarchived
+    pub fn is_archived(&self, roomid: i32) -> bool {
+        self.glavra.store.get_room(roomid).is_some_and(|room| room.archived)
+    }
+
+    // who's in a direct message conversation never changes, so anything that
+    // would let someone else in is refused there
+    pub fn is_direct(&self, roomid: i32) -> bool {
+        self.glavra.store.get_room(roomid).is_some_and(|room| room.direct)
+    }
+
+    pub fn privilege_frame(&self, privilege: &Privilege) -> PrivilegeFrame {
+        PrivilegeFrame {
+            roomid: privilege.roomid,
+            privtype: privilege.privtype.name().to_string(),
+            userid: privilege.userid,
+            threshold: privilege.threshold,
+            period: privilege.period
+        }
+    }
+
+    pub fn starboard_frame(&self, roomid: i32, votetype: VoteType)
+            -> Response {
+        Response::Starboard {
+            roomid,
+            votetype: votetype_to_int(&votetype),
+            messages: self.glavra.store.starboard(roomid, &votetype)
+                    .into_iter().map(|starred| StarredFrame {
+                id: starred.id,
+                text: starred.text,
+                timestamp: starred.timestamp.sec,
+                userid: starred.userid.unwrap_or(-1),
+                username: starred.username.unwrap_or_else(String::new),
+                votecount: starred.votecount
+            }).collect()
+        }
+    }
+
+    pub fn history_frame(&self, id: i32, roomid: i32) -> Response {
+        Response::History {
+            roomid,
+            revisions: self.glavra.store.history(id).into_iter()
+                .map(|revision| RevisionFrame {
+                    replyid: revision.replyid,
+                    text: revision.text,
+                    timestamp: revision.timestamp.sec
+                }).collect()
+        }
+    }
+
+    // how many seconds this connection has to wait before it can try a
+    // password again, if it's failed too often lately (reset codes aren't
+    // for any username, so they only go by address)
+    pub fn login_wait(&self, username: Option<&str>) -> Option<i64> {
+        let now = time::get_time();
+        let by_addr = self.addr.as_ref().and_then(|addr|
+            self.glavra.failed_addrs.wait(addr, now));
+        let by_user = username.and_then(|username|
+            self.glavra.failed_users.wait(username, now));
+        cmp::max(by_addr, by_user)
+    }
+
+    pub fn login_failed(&self, username: Option<&str>) {
+        let now = time::get_time();
+        let config = &self.glavra.config;
+        if let Some(ref addr) = self.addr {
+            self.glavra.failed_addrs.fail(addr, now, config.login_attempts,
+                config.login_lockout);
+        }
+        if let Some(username) = username {
+            self.glavra.failed_users.fail(username, now,
+                config.login_attempts, config.login_lockout);
+        }
+    }
+
+    // a new session for the user, every login getting its own token
+    pub fn start_session(&self, userid: i32, label: Option<String>)
+            -> (i32, String) {
+        let token = util::random_token();
+        let now = time::get_time();
+        let id = self.glavra.store.create_session(userid, &token,
+            &label.unwrap_or_default(), now,
+            now + Duration::seconds(self.glavra.config.session_lifetime));
+        (id, token)
+    }
+
+    // logs out every connection using these (just revoked) sessions
+    pub fn end_sessions(&self, ids: &[i32]) {
+        for &id in ids {
+            self.glavra.sessions.broadcast(id,
+                Response::Logout { id }.to_json());
+            self.glavra.sessions.close(id);
+        }
+    }
+
+    pub fn session_frame(&self, session: &Session) -> SessionFrame {
+        SessionFrame {
+            id: session.id,
+            label: session.label.clone(),
+            created: session.created.sec,
+            lastused: session.lastused.sec,
+            expires: session.expires.sec,
+            current: self.session == Some(session.id)
+        }
     }
 }
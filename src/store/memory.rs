@@ -0,0 +1,639 @@
+use time::Timespec;
+
+use types::message::*;
+use types::vote::*;
+use types::user::*;
+use types::room::*;
+use types::search::*;
+use types::privilege::*;
+use types::notification::*;
+use types::session::*;
+use enums::privtype::*;
+use enums::role::*;
+use enums::visibility::*;
+
+use util;
+
+use super::Store;
+
+use std::cmp;
+use std::sync::Mutex;
+
+#[derive(Default)]
+struct Data {
+    users: Vec<User>,
+    // (token, session)
+    sessions: Vec<(String, Session)>,
+    // (code, userid, expires)
+    resets: Vec<(String, i32, Timespec)>,
+    rooms: Vec<Room>,
+    privileges: Vec<Privilege>,
+    // (roomid, userid, role)
+    members: Vec<(i32, i32, Role)>,
+    // (token, roomid)
+    invites: Vec<(String, i32)>,
+    messages: Vec<Message>,
+    history: Vec<Revision>,
+    votes: Vec<Vote>,
+    notifications: Vec<Notification>,
+    // (userid, roomid, messageid)
+    readmarkers: Vec<(i32, i32, i32)>,
+    // votes get deleted, so their ids can't just be the index
+    next_voteid: i32
+}
+
+// keeps everything in plain vectors behind a lock; nothing survives a
+// restart, which is exactly what the tests want
+//
+// ids start at 1 and users/rooms/messages are never removed, so the id of
+// everything in those vectors is its index plus one
+pub struct MemoryStore {
+    data: Mutex<Data>
+}
+
+impl Default for MemoryStore {
+    fn default() -> MemoryStore {
+        MemoryStore::new()
+    }
+}
+
+impl MemoryStore {
+
+    pub fn new() -> MemoryStore {
+        MemoryStore {
+            data: Mutex::new(Data::default())
+        }
+    }
+
+}
+
+fn by_id<T>(items: &[T], id: i32) -> Option<&T> {
+    if id < 1 { None } else { items.get((id - 1) as usize) }
+}
+
+fn after(timestamp: &Timespec, since: &Timespec) -> bool {
+    timestamp >= since
+}
+
+// a poor man's full-text search: every word of the query has to show up
+// somewhere in the text (ignoring ascii case), and the more often they do the
+// better the rank
+//
+// returns the text (escaped) with the matches marked, along with that rank
+fn highlight(text: &str, words: &[String]) -> Option<(String, f32)> {
+    let lower = text.to_ascii_lowercase();
+    let mut matches: Vec<(usize, usize)> = Vec::new();
+    for word in words {
+        let found: Vec<_> = lower.match_indices(&word[..])
+            .map(|(i, w)| (i, i + w.len())).collect();
+        if found.is_empty() { return None; }
+        matches.extend(found);
+    }
+    matches.sort();
+
+    let mut snippet = String::new();
+    let mut pos = 0;
+    for (start, end) in matches.iter().cloned() {
+        // overlapping matches just get folded into the previous one
+        if start < pos { continue; }
+        snippet.push_str(&util::escape_html(&text[pos..start]));
+        snippet.push_str("<mark>");
+        snippet.push_str(&util::escape_html(&text[start..end]));
+        snippet.push_str("</mark>");
+        pos = end;
+    }
+    snippet.push_str(&util::escape_html(&text[pos..]));
+    Some((snippet, matches.len() as f32))
+}
+
+impl Store for MemoryStore {
+
+    fn get_user(&self, userid: i32) -> Option<User> {
+        by_id(&self.data.lock().unwrap().users, userid).cloned()
+    }
+
+    fn find_user(&self, username: &str) -> Option<User> {
+        self.data.lock().unwrap().users.iter()
+            .find(|u| u.username == username).cloned()
+    }
+
+    fn create_user(&self, username: &str, password: &str) -> Option<i32> {
+        let mut data = self.data.lock().unwrap();
+        if data.users.iter().any(|u| u.username == username) {
+            return None;
+        }
+        let id = data.users.len() as i32 + 1;
+        data.users.push(User {
+            id,
+            username: username.to_string(),
+            password: password.to_string(),
+            theme: String::from("dark")
+        });
+        Some(id)
+    }
+
+    fn set_password(&self, userid: i32, password: &str) {
+        let mut data = self.data.lock().unwrap();
+        if let Some(user) = data.users.iter_mut().find(|u| u.id == userid) {
+            user.password = password.to_string();
+        }
+    }
+
+    fn list_users(&self) -> Vec<User> {
+        self.data.lock().unwrap().users.iter().rev().cloned().collect()
+    }
+
+    fn create_session(&self, userid: i32, token: &str, label: &str,
+                      created: Timespec, expires: Timespec) -> i32 {
+        let mut data = self.data.lock().unwrap();
+        let id = data.sessions.len() as i32 + 1;
+        data.sessions.push((token.to_string(), Session {
+            id,
+            userid,
+            label: label.to_string(),
+            created,
+            lastused: created,
+            expires,
+            revoked: false
+        }));
+        id
+    }
+
+    fn find_session(&self, token: &str) -> Option<Session> {
+        self.data.lock().unwrap().sessions.iter()
+            .find(|&(t, _)| t == token)
+            .map(|(_, session)| session.clone())
+    }
+
+    fn touch_session(&self, id: i32, lastused: Timespec) {
+        let mut data = self.data.lock().unwrap();
+        if let Some(entry) = data.sessions.iter_mut()
+                .find(|entry| entry.1.id == id) {
+            entry.1.lastused = lastused;
+        }
+    }
+
+    fn list_sessions(&self, userid: i32) -> Vec<Session> {
+        self.data.lock().unwrap().sessions.iter().rev()
+            .map(|(_, session)| session)
+            .filter(|session| session.userid == userid && !session.revoked)
+            .cloned().collect()
+    }
+
+    fn revoke_session(&self, userid: i32, id: i32) -> bool {
+        let mut data = self.data.lock().unwrap();
+        match data.sessions.iter_mut().map(|entry| &mut entry.1)
+                .find(|session| session.id == id && session.userid == userid &&
+                    !session.revoked) {
+            Some(session) => {
+                session.revoked = true;
+                true
+            },
+            None => false
+        }
+    }
+
+    fn revoke_sessions(&self, userid: i32, keep: Option<i32>) -> Vec<i32> {
+        let mut data = self.data.lock().unwrap();
+        let mut ids = Vec::new();
+        for session in data.sessions.iter_mut().map(|entry| &mut entry.1) {
+            if session.userid == userid && !session.revoked &&
+                    Some(session.id) != keep {
+                session.revoked = true;
+                ids.push(session.id);
+            }
+        }
+        ids
+    }
+
+    fn create_reset(&self, userid: i32, code: &str, expires: Timespec) {
+        let mut data = self.data.lock().unwrap();
+        data.resets.retain(|&(_, u, _)| u != userid);
+        data.resets.push((code.to_string(), userid, expires));
+    }
+
+    fn redeem_reset(&self, code: &str, now: Timespec) -> Option<i32> {
+        let mut data = self.data.lock().unwrap();
+        let index = data.resets.iter().position(|(c, _, _)| c == code);
+        match index.map(|index| data.resets.remove(index)) {
+            Some((_, userid, expires)) if expires > now => Some(userid),
+            _ => None
+        }
+    }
+
+    fn create_room(&self, name: &str, description: &str) -> i32 {
+        let mut data = self.data.lock().unwrap();
+        let id = data.rooms.len() as i32 + 1;
+        data.rooms.push(Room {
+            id,
+            name: name.to_string(),
+            description: description.to_string(),
+            topic: String::new(),
+            archived: false,
+            visibility: Visibility::Public,
+            direct: false
+        });
+        id
+    }
+
+    fn get_room(&self, roomid: i32) -> Option<Room> {
+        by_id(&self.data.lock().unwrap().rooms, roomid).cloned()
+    }
+
+    fn list_rooms(&self) -> Vec<Room> {
+        self.data.lock().unwrap().rooms.iter().rev().cloned().collect()
+    }
+
+    fn edit_room(&self, roomid: i32, name: &str, description: &str,
+            topic: &str) {
+        let mut data = self.data.lock().unwrap();
+        if let Some(room) = data.rooms.iter_mut().find(|r| r.id == roomid) {
+            room.name = name.to_string();
+            room.description = description.to_string();
+            room.topic = topic.to_string();
+        }
+    }
+
+    fn set_archived(&self, roomid: i32, archived: bool) {
+        let mut data = self.data.lock().unwrap();
+        if let Some(room) = data.rooms.iter_mut().find(|r| r.id == roomid) {
+            room.archived = archived;
+        }
+    }
+
+    fn set_visibility(&self, roomid: i32, visibility: Visibility) {
+        let mut data = self.data.lock().unwrap();
+        if let Some(room) = data.rooms.iter_mut().find(|r| r.id == roomid) {
+            room.visibility = visibility;
+        }
+    }
+
+    fn create_direct(&self, userids: &[i32]) -> i32 {
+        let mut data = self.data.lock().unwrap();
+        let id = data.rooms.len() as i32 + 1;
+        data.rooms.push(Room {
+            id,
+            name: String::new(),
+            description: String::new(),
+            topic: String::new(),
+            archived: false,
+            visibility: Visibility::Invite,
+            direct: true
+        });
+        for &userid in userids {
+            data.members.push((id, userid, Role::Member));
+        }
+        id
+    }
+
+    fn find_direct(&self, userids: &[i32]) -> Option<i32> {
+        let data = self.data.lock().unwrap();
+        data.rooms.iter().filter(|r| r.direct).find(|r| {
+            let mut members: Vec<i32> = data.members.iter()
+                .filter(|&&(roomid, _, _)| roomid == r.id)
+                .map(|&(_, userid, _)| userid).collect();
+            members.sort();
+            &members[..] == userids
+        }).map(|r| r.id)
+    }
+
+    fn list_direct(&self, userid: i32) -> Vec<i32> {
+        let data = self.data.lock().unwrap();
+        data.rooms.iter().rev().filter(|r| r.direct && data.members.iter()
+                .any(|&(roomid, u, _)| roomid == r.id && u == userid))
+            .map(|r| r.id).collect()
+    }
+
+    fn create_invite(&self, roomid: i32, _userid: i32, token: &str) {
+        self.data.lock().unwrap().invites.push((token.to_string(), roomid));
+    }
+
+    fn invite_room(&self, token: &str) -> Option<i32> {
+        self.data.lock().unwrap().invites.iter()
+            .find(|&(t, _)| t == token).map(|&(_, roomid)| roomid)
+    }
+
+    fn add_privilege(&self, roomid: i32, userid: Option<i32>,
+            privtype: PrivType, threshold: i32, period: i32) {
+        self.data.lock().unwrap().privileges.push(Privilege {
+            roomid,
+            userid,
+            privtype,
+            threshold,
+            period
+        });
+    }
+
+    fn get_privilege(&self, roomid: i32, userid: Option<i32>,
+            privtype: PrivType) -> Option<(i64, f64)> {
+        let data = self.data.lock().unwrap();
+        data.privileges.iter()
+            .find(|p| p.roomid == roomid && p.userid == userid &&
+                p.privtype == privtype)
+            .map(|p| (p.threshold as i64, p.period as f64))
+    }
+
+    fn set_privilege(&self, roomid: i32, userid: Option<i32>,
+            privtype: PrivType, threshold: i32, period: i32) {
+        self.data.lock().unwrap().privileges.retain(|p| !(p.roomid == roomid &&
+            p.userid == userid && p.privtype == privtype));
+        self.add_privilege(roomid, userid, privtype, threshold, period);
+    }
+
+    fn clear_privilege(&self, roomid: i32, userid: Option<i32>,
+            privtype: PrivType) -> bool {
+        let mut data = self.data.lock().unwrap();
+        let before = data.privileges.len();
+        data.privileges.retain(|p| !(p.roomid == roomid &&
+            p.userid == userid && p.privtype == privtype));
+        data.privileges.len() != before
+    }
+
+    fn list_privileges(&self, roomid: i32) -> Vec<Privilege> {
+        let mut privileges: Vec<Privilege> = self.data.lock().unwrap()
+            .privileges.iter().filter(|p| p.roomid == roomid).cloned()
+            .collect();
+        privileges.sort_by_key(|p| (p.userid, p.privtype as i32));
+        privileges
+    }
+
+    fn get_role(&self, roomid: i32, userid: i32) -> Option<Role> {
+        self.data.lock().unwrap().members.iter()
+            .find(|&&(r, u, _)| r == roomid && u == userid)
+            .map(|&(_, _, role)| role)
+    }
+
+    fn set_role(&self, roomid: i32, userid: i32, role: Role) {
+        let mut data = self.data.lock().unwrap();
+        data.members.retain(|&(r, u, _)| !(r == roomid && u == userid));
+        data.members.push((roomid, userid, role));
+    }
+
+    fn list_members(&self, roomid: i32) -> Vec<(i32, Role)> {
+        let mut members: Vec<(i32, Role)> = self.data.lock().unwrap()
+            .members.iter().filter(|&&(r, _, _)| r == roomid)
+            .map(|&(_, u, role)| (u, role)).collect();
+        members.sort_by_key(|&(u, _)| u);
+        members
+    }
+
+    fn insert_message(&self, message: &Message) -> i32 {
+        let mut data = self.data.lock().unwrap();
+        let mut message = message.clone();
+        message.id = data.messages.len() as i32 + 1;
+        data.messages.push(message);
+        data.messages.len() as i32
+    }
+
+    fn get_message(&self, messageid: i32) -> Option<Message> {
+        by_id(&self.data.lock().unwrap().messages, messageid).cloned()
+    }
+
+    fn edit_message(&self, messageid: i32, replyid: Option<i32>, text: &str,
+            timestamp: Timespec) {
+        let mut data = self.data.lock().unwrap();
+        let revision = match by_id(&data.messages, messageid) {
+            Some(m) => Revision {
+                messageid,
+                replyid: m.replyid,
+                text: m.text.clone(),
+                timestamp
+            },
+            None => return
+        };
+        data.history.push(revision);
+        let message = &mut data.messages[(messageid - 1) as usize];
+        message.replyid = replyid;
+        message.text = text.to_string();
+    }
+
+    fn recent_messages(&self, roomid: i32, limit: i64) -> Vec<Message> {
+        let data = self.data.lock().unwrap();
+        let mut messages: Vec<Message> = data.messages.iter().rev()
+            .filter(|m| m.roomid == roomid)
+            .take(limit as usize).cloned().collect();
+        messages.reverse();
+        messages
+    }
+
+    fn messages_before(&self, roomid: i32, messageid: i32, limit: i64)
+            -> Vec<Message> {
+        let data = self.data.lock().unwrap();
+        let mut messages: Vec<Message> = data.messages.iter().rev()
+            .filter(|m| m.roomid == roomid && m.id < messageid)
+            .take(limit as usize).cloned().collect();
+        messages.reverse();
+        messages
+    }
+
+    fn messages_after(&self, roomid: i32, messageid: i32, limit: i64)
+            -> Vec<Message> {
+        self.data.lock().unwrap().messages.iter()
+            .filter(|m| m.roomid == roomid && m.id > messageid)
+            .take(limit as usize).cloned().collect()
+    }
+
+    fn replies(&self, messageid: i32, roomid: i32) -> Vec<Message> {
+        self.data.lock().unwrap().messages.iter()
+            .filter(|m| m.replyid == Some(messageid) && m.roomid == roomid &&
+                !m.text.is_empty())
+            .cloned().collect()
+    }
+
+    fn count_replies(&self, messageid: i32, roomid: i32) -> i64 {
+        self.data.lock().unwrap().messages.iter()
+            .filter(|m| m.replyid == Some(messageid) && m.roomid == roomid &&
+                !m.text.is_empty())
+            .count() as i64
+    }
+
+    fn move_messages(&self, messageids: &[i32], roomid: i32) {
+        let mut data = self.data.lock().unwrap();
+        for message in data.messages.iter_mut()
+                .filter(|m| messageids.contains(&m.id)) {
+            message.roomid = roomid;
+        }
+    }
+
+    fn history(&self, messageid: i32) -> Vec<Revision> {
+        self.data.lock().unwrap().history.iter()
+            .filter(|r| r.messageid == messageid).cloned().collect()
+    }
+
+    fn search(&self, query: &SearchQuery) -> Vec<SearchResult> {
+        let words: Vec<String> = query.text.split_whitespace()
+            .map(|w| w.to_ascii_lowercase()).collect();
+        if words.is_empty() { return Vec::new(); }
+
+        let data = self.data.lock().unwrap();
+        let mut results: Vec<SearchResult> = data.messages.iter().rev()
+            .filter(|m| query.roomids.contains(&m.roomid))
+            .filter(|m| query.userid.is_none_or(|u| m.userid == u))
+            .filter(|m| query.since.is_none_or(|t| m.timestamp >= t))
+            .filter(|m| query.until.is_none_or(|t| m.timestamp < t))
+            .filter(|m| query.hasreply.is_none_or(|r| m.replyid.is_some() == r))
+            .filter_map(|m| highlight(&m.text, &words).map(|(snippet, rank)|
+                SearchResult {
+                    message: m.clone(),
+                    snippet,
+                    rank
+                }))
+            .collect();
+        // stable, so equally ranked results stay newest first
+        results.sort_by(|a, b| b.rank.partial_cmp(&a.rank).unwrap());
+        results.truncate(query.limit as usize);
+        results
+    }
+
+    fn add_notification(&self, userid: i32, messageid: i32, kind: NotifyKind,
+            timestamp: Timespec) -> Option<i32> {
+        let mut data = self.data.lock().unwrap();
+        if data.notifications.iter().any(|n| n.userid == userid &&
+                n.messageid == messageid && n.kind == kind) {
+            return None;
+        }
+        let id = data.notifications.len() as i32 + 1;
+        data.notifications.push(Notification {
+            id,
+            userid,
+            messageid,
+            kind,
+            timestamp,
+            read: false
+        });
+        Some(id)
+    }
+
+    fn unread_notifications(&self, userid: i32, limit: i64)
+            -> Vec<Notification> {
+        self.data.lock().unwrap().notifications.iter().rev()
+            .filter(|n| n.userid == userid && !n.read)
+            .take(limit as usize).cloned().collect()
+    }
+
+    fn mark_notifications_read(&self, userid: i32, ids: Option<&[i32]>) {
+        let mut data = self.data.lock().unwrap();
+        for notification in data.notifications.iter_mut()
+                .filter(|n| n.userid == userid &&
+                    ids.is_none_or(|ids| ids.contains(&n.id))) {
+            notification.read = true;
+        }
+    }
+
+    fn get_read_marker(&self, userid: i32, roomid: i32) -> Option<i32> {
+        self.data.lock().unwrap().readmarkers.iter()
+            .find(|&&(u, r, _)| u == userid && r == roomid)
+            .map(|&(_, _, messageid)| messageid)
+    }
+
+    fn set_read_marker(&self, userid: i32, roomid: i32, messageid: i32) {
+        let mut data = self.data.lock().unwrap();
+        match data.readmarkers.iter_mut()
+                .find(|marker| marker.0 == userid && marker.1 == roomid) {
+            Some(marker) => marker.2 = marker.2.max(messageid),
+            None => data.readmarkers.push((userid, roomid, messageid))
+        }
+    }
+
+    fn count_unread(&self, userid: i32, roomid: i32) -> i64 {
+        let marker = self.get_read_marker(userid, roomid).unwrap_or(0);
+        self.data.lock().unwrap().messages.iter()
+            .filter(|m| m.roomid == roomid && m.userid != userid &&
+                m.userid != -1 && !m.text.is_empty() && m.id > marker)
+            .count() as i64
+    }
+
+    fn find_vote(&self, messageid: i32, userid: i32, votetype: &VoteType)
+            -> Option<i32> {
+        self.data.lock().unwrap().votes.iter()
+            .find(|v| v.messageid == messageid && v.userid == userid &&
+                &v.votetype == votetype)
+            .map(|v| v.id)
+    }
+
+    fn insert_vote(&self, vote: &Vote) -> i32 {
+        let mut data = self.data.lock().unwrap();
+        data.next_voteid += 1;
+        let mut vote = vote.clone();
+        vote.id = data.next_voteid;
+        data.votes.push(vote);
+        data.next_voteid
+    }
+
+    fn delete_vote(&self, voteid: i32) {
+        self.data.lock().unwrap().votes.retain(|v| v.id != voteid);
+    }
+
+    fn message_votes(&self, messageid: i32) -> Vec<Vote> {
+        self.data.lock().unwrap().votes.iter()
+            .filter(|v| v.messageid == messageid).cloned().collect()
+    }
+
+    fn starboard(&self, roomid: i32, votetype: &VoteType)
+            -> Vec<StarredMessage> {
+        let data = self.data.lock().unwrap();
+        let mut starred: Vec<StarredMessage> = data.messages.iter()
+            .filter(|m| m.roomid == roomid)
+            .map(|m| StarredMessage {
+                id: m.id,
+                text: m.text.clone(),
+                timestamp: m.timestamp,
+                userid: by_id(&data.users, m.userid).map(|u| u.id),
+                username: by_id(&data.users, m.userid)
+                    .map(|u| u.username.clone()),
+                votecount: data.votes.iter().filter(|v| v.messageid == m.id &&
+                    &v.votetype == votetype).count() as i64
+            })
+            .filter(|s| s.votecount > 0)
+            .collect();
+        // no decay here, unlike the postgres starboard; most votes wins
+        starred.sort_by_key(|s| cmp::Reverse(s.votecount));
+        if votetype == &VoteType::Star {
+            starred.truncate(10);
+        } else {
+            starred.reverse();
+        }
+        starred
+    }
+
+    fn count_messages(&self, roomid: i32, userid: i32, since: Timespec) -> i64 {
+        self.data.lock().unwrap().messages.iter()
+            .filter(|m| m.roomid == roomid && m.userid == userid &&
+                after(&m.timestamp, &since))
+            .count() as i64
+    }
+
+    fn count_edits(&self, roomid: i32, userid: i32, since: Timespec) -> i64 {
+        let data = self.data.lock().unwrap();
+        data.history.iter()
+            .filter(|r| after(&r.timestamp, &since))
+            .filter(|r| by_id(&data.messages, r.messageid).is_some_and(|m|
+                m.roomid == roomid && m.userid == userid && !m.text.is_empty()))
+            .count() as i64
+    }
+
+    fn count_deletes(&self, roomid: i32, userid: i32, since: Timespec) -> i64 {
+        let data = self.data.lock().unwrap();
+        let mut ids: Vec<i32> = data.history.iter()
+            .filter(|r| after(&r.timestamp, &since))
+            .filter(|r| by_id(&data.messages, r.messageid).is_some_and(|m|
+                m.roomid == roomid && m.userid == userid && m.text.is_empty()))
+            .map(|r| r.messageid)
+            .collect();
+        ids.sort();
+        ids.dedup();
+        ids.len() as i64
+    }
+
+    fn count_votes(&self, roomid: i32, userid: i32, votetype: &VoteType,
+            since: Timespec) -> i64 {
+        let data = self.data.lock().unwrap();
+        data.votes.iter()
+            .filter(|v| v.userid == userid && &v.votetype == votetype &&
+                after(&v.timestamp, &since))
+            .filter(|v| by_id(&data.messages, v.messageid)
+                .is_some_and(|m| m.roomid == roomid))
+            .count() as i64
+    }
+
+}
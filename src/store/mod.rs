@@ -0,0 +1,167 @@
+use time::Timespec;
+
+use types::message::*;
+use types::vote::*;
+use types::user::*;
+use types::room::*;
+use types::search::*;
+use types::privilege::*;
+use types::notification::*;
+use types::session::*;
+use enums::privtype::*;
+use enums::role::*;
+use enums::visibility::*;
+
+pub mod pg;
+pub mod memory;
+
+pub use self::pg::PgStore;
+pub use self::memory::MemoryStore;
+
+// what a store panics with when it gives up on reaching wherever it keeps
+// things; the connection turns it into an error frame instead of going down
+pub struct Unavailable;
+
+// everything the server needs to persist goes through here, so that handlers
+// don't care whether they're talking to postgres or to a bunch of vectors
+//
+// ids are assigned by the store; methods that look something up by id return
+// None when it doesn't exist
+pub trait Store: Send + Sync {
+
+    // users
+    fn get_user(&self, userid: i32) -> Option<User>;
+    fn find_user(&self, username: &str) -> Option<User>;
+    // returns None if the username is already taken
+    fn create_user(&self, username: &str, password: &str) -> Option<i32>;
+    fn set_password(&self, userid: i32, password: &str);
+    // newest first
+    fn list_users(&self) -> Vec<User>;
+
+    // sessions, each with the token it's logged in with
+    fn create_session(&self, userid: i32, token: &str, label: &str,
+        created: Timespec, expires: Timespec) -> i32;
+    // revoked and expired sessions included, so callers can tell them apart
+    // from tokens that never existed
+    fn find_session(&self, token: &str) -> Option<Session>;
+    fn touch_session(&self, id: i32, lastused: Timespec);
+    // the user's sessions that haven't been revoked, newest first
+    fn list_sessions(&self, userid: i32) -> Vec<Session>;
+    // returns false if the user has no such session, or it's already revoked
+    fn revoke_session(&self, userid: i32, id: i32) -> bool;
+    // revokes every session of the user but `keep`, and returns their ids
+    fn revoke_sessions(&self, userid: i32, keep: Option<i32>) -> Vec<i32>;
+
+    // password reset codes; making one for a user throws away any they
+    // already had
+    fn create_reset(&self, userid: i32, code: &str, expires: Timespec);
+    // uses the code up, and returns who it was for if it hadn't expired
+    fn redeem_reset(&self, code: &str, now: Timespec) -> Option<i32>;
+
+    // rooms
+    fn create_room(&self, name: &str, description: &str) -> i32;
+    fn get_room(&self, roomid: i32) -> Option<Room>;
+    // newest first
+    fn list_rooms(&self) -> Vec<Room>;
+    fn edit_room(&self, roomid: i32, name: &str, description: &str,
+        topic: &str);
+    fn set_archived(&self, roomid: i32, archived: bool);
+    fn set_visibility(&self, roomid: i32, visibility: Visibility);
+
+    // direct message conversations; `userids` are all the participants,
+    // sorted
+    fn create_direct(&self, userids: &[i32]) -> i32;
+    fn find_direct(&self, userids: &[i32]) -> Option<i32>;
+    // the ids of every conversation the user is part of, newest first
+    fn list_direct(&self, userid: i32) -> Vec<i32>;
+
+    // invites; tokens stay valid until the room is gone
+    fn create_invite(&self, roomid: i32, userid: i32, token: &str);
+    // the room an invite token is for
+    fn invite_room(&self, token: &str) -> Option<i32>;
+
+    // privileges
+    fn add_privilege(&self, roomid: i32, userid: Option<i32>,
+        privtype: PrivType, threshold: i32, period: i32);
+    // the row for exactly this user, or the room-wide one for None; returns
+    // (threshold, period in seconds)
+    fn get_privilege(&self, roomid: i32, userid: Option<i32>,
+        privtype: PrivType) -> Option<(i64, f64)>;
+    // like add_privilege, but replaces the row for the same room, user (or
+    // lack thereof) and privtype if there is one
+    fn set_privilege(&self, roomid: i32, userid: Option<i32>,
+        privtype: PrivType, threshold: i32, period: i32);
+    // returns whether there was such a row
+    fn clear_privilege(&self, roomid: i32, userid: Option<i32>,
+        privtype: PrivType) -> bool;
+    // room-wide rows first, then per-user ones
+    fn list_privileges(&self, roomid: i32) -> Vec<Privilege>;
+
+    // room membership
+    fn get_role(&self, roomid: i32, userid: i32) -> Option<Role>;
+    fn set_role(&self, roomid: i32, userid: i32, role: Role);
+    // (userid, role), by userid
+    fn list_members(&self, roomid: i32) -> Vec<(i32, Role)>;
+
+    // messages
+    fn insert_message(&self, message: &Message) -> i32;
+    fn get_message(&self, messageid: i32) -> Option<Message>;
+    // saves the current version of the message as a revision, then replaces
+    // its reply and text
+    fn edit_message(&self, messageid: i32, replyid: Option<i32>, text: &str,
+        timestamp: Timespec);
+    // the last `limit` messages in the room, oldest first
+    fn recent_messages(&self, roomid: i32, limit: i64) -> Vec<Message>;
+    // the last `limit` messages in the room older than `messageid`, and the
+    // first `limit` newer than it; both oldest first
+    fn messages_before(&self, roomid: i32, messageid: i32, limit: i64)
+        -> Vec<Message>;
+    fn messages_after(&self, roomid: i32, messageid: i32, limit: i64)
+        -> Vec<Message>;
+    fn history(&self, messageid: i32) -> Vec<Revision>;
+    // the undeleted direct replies to a message, oldest first; only the ones
+    // in `roomid` (the message's own room) count, since replies that were
+    // moved out may have gone somewhere the reader can't see
+    fn replies(&self, messageid: i32, roomid: i32) -> Vec<Message>;
+    fn count_replies(&self, messageid: i32, roomid: i32) -> i64;
+    // votes and history follow along, since they only refer to the message
+    fn move_messages(&self, messageids: &[i32], roomid: i32);
+    // best matches first; deleted messages never match
+    fn search(&self, query: &SearchQuery) -> Vec<SearchResult>;
+
+    // notifications; a user is only ever notified once per message and kind,
+    // so adding one that already exists returns None
+    fn add_notification(&self, userid: i32, messageid: i32, kind: NotifyKind,
+        timestamp: Timespec) -> Option<i32>;
+    // newest first
+    fn unread_notifications(&self, userid: i32, limit: i64)
+        -> Vec<Notification>;
+    // all of the user's notifications if `ids` is None
+    fn mark_notifications_read(&self, userid: i32, ids: Option<&[i32]>);
+
+    // read markers: the last message a user has seen in a room
+    fn get_read_marker(&self, userid: i32, roomid: i32) -> Option<i32>;
+    // never moves the marker backwards
+    fn set_read_marker(&self, userid: i32, roomid: i32, messageid: i32);
+    // undeleted messages by other people (so not system messages either)
+    // after the user's read marker, or all of them without one
+    fn count_unread(&self, userid: i32, roomid: i32) -> i64;
+
+    // votes
+    fn find_vote(&self, messageid: i32, userid: i32, votetype: &VoteType)
+        -> Option<i32>;
+    fn insert_vote(&self, vote: &Vote) -> i32;
+    fn delete_vote(&self, voteid: i32);
+    fn message_votes(&self, messageid: i32) -> Vec<Vote>;
+    fn starboard(&self, roomid: i32, votetype: &VoteType)
+        -> Vec<StarredMessage>;
+
+    // rate limiting: how many of each action a user has taken in a room since
+    // the given time
+    fn count_messages(&self, roomid: i32, userid: i32, since: Timespec) -> i64;
+    fn count_edits(&self, roomid: i32, userid: i32, since: Timespec) -> i64;
+    fn count_deletes(&self, roomid: i32, userid: i32, since: Timespec) -> i64;
+    fn count_votes(&self, roomid: i32, userid: i32, votetype: &VoteType,
+        since: Timespec) -> i64;
+
+}
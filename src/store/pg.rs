@@ -0,0 +1,766 @@
+use postgres::rows::Row;
+
+use r2d2;
+use r2d2_postgres::{PostgresConnectionManager, TlsMode};
+
+use time;
+use time::Timespec;
+
+use config::Config;
+use util;
+use migrations;
+use migrations::SchemaError;
+
+use types::message::*;
+use types::vote::*;
+use types::user::*;
+use types::room::*;
+use types::search::*;
+use types::privilege::*;
+use types::notification::*;
+use types::session::*;
+use enums::privtype::*;
+use enums::role::*;
+use enums::visibility::*;
+
+use super::{Store, Unavailable};
+
+use std::panic;
+
+type PooledConnection = r2d2::PooledConnection<PostgresConnectionManager>;
+
+const CONN_ATTEMPTS: u32 = 3;
+
+// chr(2) and chr(3) on the postgres side
+const START_SEL: char = '\u{2}';
+const STOP_SEL: char = '\u{3}';
+
+pub struct PgStore {
+    pool: r2d2::Pool<PostgresConnectionManager>
+}
+
+impl PgStore {
+
+    // refuses to hand back a store if the database schema doesn't match what
+    // this build expects, or if there's no database to talk to at all
+    pub fn new(config: &Config) -> Result<PgStore, SchemaError> {
+        let manager = PostgresConnectionManager::new(
+            &config.database_url[..], TlsMode::None)?;
+        let pool = r2d2::Pool::builder()
+            .max_size(config.pool_size)
+            .build(manager)?;
+        migrations::check(&*pool.get()?)?;
+        Ok(PgStore { pool })
+    }
+
+    // checks a connection out of the pool; it goes back in when dropped
+    //
+    // each try waits out the pool's timeout, and if the database is still
+    // gone after a few of them the request fails with Unavailable
+    fn conn(&self) -> PooledConnection {
+        for attempt in 1..=CONN_ATTEMPTS {
+            match self.pool.get() {
+                Ok(conn) => return conn,
+                Err(e) => error!("couldn't get a database connection \
+                    ({} of {}): {}", attempt, CONN_ATTEMPTS, e)
+            }
+        }
+        panic::panic_any(Unavailable)
+    }
+
+}
+
+fn user_from_row(row: Row) -> User {
+    User {
+        id: row.get(0),
+        username: row.get(1),
+        password: row.get(2),
+        theme: row.get(3)
+    }
+}
+
+fn room_from_row(row: Row) -> Room {
+    Room {
+        id: row.get(0),
+        name: row.get(1),
+        description: row.get(2),
+        topic: row.get(3),
+        archived: row.get(4),
+        visibility: Visibility::from_i32(row.get(5))
+            .unwrap_or(Visibility::Invite),
+        direct: row.get(6)
+    }
+}
+
+fn message_from_row(row: Row) -> Message {
+    Message {
+        id: row.get(0),
+        roomid: row.get(1),
+        userid: row.get(2),
+        replyid: row.get(3),
+        text: row.get(4),
+        timestamp: row.get(5)
+    }
+}
+
+// rows with a privtype this build doesn't know about are skipped
+fn privilege_from_row(row: Row) -> Option<Privilege> {
+    PrivType::from_i32(row.get(2)).map(|privtype| Privilege {
+        roomid: row.get(0),
+        userid: row.get(1),
+        privtype,
+        threshold: row.get(3),
+        period: row.get::<usize, f32>(4) as i32
+    })
+}
+
+// ts_headline marks matches with START_SEL and STOP_SEL (which are taken out
+// of the text beforehand), and only once the rest of it has been escaped do
+// they turn into <mark> tags; escaping first would have it mark the "amp" in
+// &amp; whenever someone searches for amp, cutting the entity in half
+fn mark(headline: &str) -> String {
+    util::escape_html(headline)
+        .replace(START_SEL, "<mark>").replace(STOP_SEL, "</mark>")
+}
+
+fn notification_from_row(row: Row) -> Notification {
+    Notification {
+        id: row.get(0),
+        userid: row.get(1),
+        messageid: row.get(2),
+        kind: int_to_notifykind(row.get(3)).unwrap(),
+        timestamp: row.get(4),
+        read: row.get(5)
+    }
+}
+
+fn session_from_row(row: Row) -> Session {
+    Session {
+        id: row.get(0),
+        userid: row.get(1),
+        label: row.get(2),
+        created: row.get(3),
+        lastused: row.get(4),
+        expires: row.get(5),
+        revoked: row.get(6)
+    }
+}
+
+fn vote_from_row(row: Row) -> Vote {
+    Vote {
+        id: row.get(0),
+        messageid: row.get(1),
+        userid: row.get(2),
+        votetype: int_to_votetype(row.get(3)).unwrap(),
+        timestamp: row.get(4)
+    }
+}
+
+impl Store for PgStore {
+
+    fn get_user(&self, userid: i32) -> Option<User> {
+        self.conn().query("
+                SELECT id, username, password, theme
+                FROM users
+                WHERE id = $1", &[&userid]).unwrap()
+            .iter().next().map(user_from_row)
+    }
+
+    fn find_user(&self, username: &str) -> Option<User> {
+        self.conn().query("
+                SELECT id, username, password, theme
+                FROM users
+                WHERE username = $1", &[&username]).unwrap()
+            .iter().next().map(user_from_row)
+    }
+
+    fn create_user(&self, username: &str, password: &str) -> Option<i32> {
+        self.conn().query("
+                INSERT INTO users (username, password, theme)
+                VALUES ($1, $2, 'dark')
+                RETURNING id", &[&username, &password])
+            .ok().map(|rows| rows.get(0).get(0))
+    }
+
+    fn set_password(&self, userid: i32, password: &str) {
+        self.conn().execute("
+                UPDATE users SET password = $2
+                WHERE id = $1", &[&userid, &password]).unwrap();
+    }
+
+    fn list_users(&self) -> Vec<User> {
+        self.conn().query("
+                SELECT id, username, password, theme
+                FROM users
+                ORDER BY id DESC", &[]).unwrap()
+            .iter().map(user_from_row).collect()
+    }
+
+    fn create_session(&self, userid: i32, token: &str, label: &str,
+                      created: Timespec, expires: Timespec) -> i32 {
+        self.conn().query("
+                INSERT INTO tokens (userid, token, label, created, lastused,
+                    expires)
+                VALUES ($1, $2, $3, $4, $4, $5)
+                RETURNING id",
+                &[&userid, &token, &label, &created, &expires]).unwrap()
+            .get(0).get(0)
+    }
+
+    fn find_session(&self, token: &str) -> Option<Session> {
+        self.conn().query("
+                SELECT id, userid, label, created, lastused, expires, revoked
+                FROM tokens
+                WHERE token = $1", &[&token]).unwrap()
+            .iter().next().map(session_from_row)
+    }
+
+    fn touch_session(&self, id: i32, lastused: Timespec) {
+        self.conn().execute("
+                UPDATE tokens SET lastused = $2
+                WHERE id = $1", &[&id, &lastused]).unwrap();
+    }
+
+    fn list_sessions(&self, userid: i32) -> Vec<Session> {
+        self.conn().query("
+                SELECT id, userid, label, created, lastused, expires, revoked
+                FROM tokens
+                WHERE userid = $1 AND NOT revoked
+                ORDER BY id DESC", &[&userid]).unwrap()
+            .iter().map(session_from_row).collect()
+    }
+
+    fn revoke_session(&self, userid: i32, id: i32) -> bool {
+        self.conn().execute("
+                UPDATE tokens SET revoked = TRUE
+                WHERE userid = $1 AND id = $2 AND NOT revoked",
+                &[&userid, &id]).unwrap() > 0
+    }
+
+    fn revoke_sessions(&self, userid: i32, keep: Option<i32>) -> Vec<i32> {
+        self.conn().query("
+                UPDATE tokens SET revoked = TRUE
+                WHERE userid = $1 AND NOT revoked
+                    AND ($2::INT IS NULL OR id <> $2)
+                RETURNING id", &[&userid, &keep]).unwrap()
+            .iter().map(|row| row.get(0)).collect()
+    }
+
+    fn create_reset(&self, userid: i32, code: &str, expires: Timespec) {
+        let conn = self.conn();
+        let trans = conn.transaction().unwrap();
+        trans.execute("
+                DELETE FROM resets
+                WHERE userid = $1", &[&userid]).unwrap();
+        trans.execute("
+                INSERT INTO resets (code, userid, expires)
+                VALUES ($1, $2, $3)", &[&code, &userid, &expires]).unwrap();
+        trans.commit().unwrap();
+    }
+
+    fn redeem_reset(&self, code: &str, now: Timespec) -> Option<i32> {
+        self.conn().query("
+                DELETE FROM resets
+                WHERE code = $1
+                RETURNING userid, expires > $2", &[&code, &now]).unwrap()
+            .iter().next().and_then(|row| {
+                let valid: bool = row.get(1);
+                if valid { Some(row.get(0)) } else { None }
+            })
+    }
+
+    fn create_room(&self, name: &str, description: &str) -> i32 {
+        self.conn().query("
+                INSERT INTO rooms (name, description) VALUES ($1, $2)
+                RETURNING id", &[&name, &description]).unwrap()
+            .get(0).get(0)
+    }
+
+    fn get_room(&self, roomid: i32) -> Option<Room> {
+        self.conn().query("
+                SELECT id, name, description, topic, archived, visibility,
+                  direct
+                FROM rooms
+                WHERE id = $1", &[&roomid]).unwrap()
+            .iter().next().map(room_from_row)
+    }
+
+    fn list_rooms(&self) -> Vec<Room> {
+        self.conn().query("
+                SELECT id, name, description, topic, archived, visibility,
+                  direct
+                FROM rooms
+                ORDER BY id DESC", &[]).unwrap()
+            .iter().map(room_from_row).collect()
+    }
+
+    fn add_privilege(&self, roomid: i32, userid: Option<i32>,
+            privtype: PrivType, threshold: i32, period: i32) {
+        self.conn().execute("
+                INSERT INTO privileges (roomid, userid, privtype, threshold, period)
+                VALUES ($1, $2, $3, $4, (interval '1s') * $5)",
+                &[&roomid, &userid, &(privtype as i32), &threshold,
+                  &(period as f64)]).unwrap();
+    }
+
+    fn edit_room(&self, roomid: i32, name: &str, description: &str,
+            topic: &str) {
+        self.conn().execute("
+                UPDATE rooms SET name = $2, description = $3, topic = $4
+                WHERE id = $1", &[&roomid, &name, &description, &topic])
+            .unwrap();
+    }
+
+    fn set_archived(&self, roomid: i32, archived: bool) {
+        self.conn().execute("
+                UPDATE rooms SET archived = $2
+                WHERE id = $1", &[&roomid, &archived]).unwrap();
+    }
+
+    fn set_visibility(&self, roomid: i32, visibility: Visibility) {
+        self.conn().execute("
+                UPDATE rooms SET visibility = $2
+                WHERE id = $1", &[&roomid, &(visibility as i32)]).unwrap();
+    }
+
+    fn create_direct(&self, userids: &[i32]) -> i32 {
+        let conn = self.conn();
+        let trans = conn.transaction().unwrap();
+        let roomid: i32 = trans.query("
+                INSERT INTO rooms (name, description, visibility, direct)
+                VALUES ('', '', $1, TRUE)
+                RETURNING id", &[&(Visibility::Invite as i32)]).unwrap()
+            .get(0).get(0);
+        for userid in userids {
+            trans.execute("
+                    INSERT INTO members (roomid, userid, role)
+                    VALUES ($1, $2, $3)",
+                    &[&roomid, userid, &(Role::Member as i32)]).unwrap();
+        }
+        trans.commit().unwrap();
+        roomid
+    }
+
+    fn find_direct(&self, userids: &[i32]) -> Option<i32> {
+        self.conn().query("
+                SELECT id FROM rooms
+                WHERE direct
+                  AND (SELECT array_agg(userid ORDER BY userid)
+                       FROM members
+                       WHERE roomid = rooms.id) = $1",
+                &[&userids]).unwrap()
+            .iter().next().map(|row| row.get(0))
+    }
+
+    fn list_direct(&self, userid: i32) -> Vec<i32> {
+        self.conn().query("
+                SELECT rooms.id
+                FROM rooms JOIN members ON members.roomid = rooms.id
+                WHERE rooms.direct AND members.userid = $1
+                ORDER BY rooms.id DESC", &[&userid]).unwrap()
+            .iter().map(|row| row.get(0)).collect()
+    }
+
+    fn create_invite(&self, roomid: i32, userid: i32, token: &str) {
+        self.conn().execute("
+                INSERT INTO invites (token, roomid, userid, tstamp)
+                VALUES ($1, $2, $3, $4)",
+                &[&token, &roomid, &userid, &time::get_time()]).unwrap();
+    }
+
+    fn invite_room(&self, token: &str) -> Option<i32> {
+        self.conn().query("
+                SELECT roomid FROM invites
+                WHERE token = $1", &[&token]).unwrap()
+            .iter().next().map(|row| row.get(0))
+    }
+
+    fn get_privilege(&self, roomid: i32, userid: Option<i32>,
+            privtype: PrivType) -> Option<(i64, f64)> {
+        self.conn().query("
+                SELECT threshold, EXTRACT(EPOCH FROM period)::REAL
+                FROM privileges
+                WHERE roomid = $1
+                  AND userid IS NOT DISTINCT FROM $2
+                  AND privtype = $3", &[&roomid, &userid, &(privtype as i32)])
+            .unwrap().iter().next().map(|row|
+                (row.get::<usize, i32>(0) as i64,
+                 row.get::<usize, f32>(1) as f64))
+    }
+
+    fn set_privilege(&self, roomid: i32, userid: Option<i32>,
+            privtype: PrivType, threshold: i32, period: i32) {
+        let conn = self.conn();
+        let trans = conn.transaction().unwrap();
+        trans.execute("
+                DELETE FROM privileges
+                WHERE roomid = $1
+                  AND userid IS NOT DISTINCT FROM $2
+                  AND privtype = $3",
+                &[&roomid, &userid, &(privtype as i32)]).unwrap();
+        trans.execute("
+                INSERT INTO privileges (roomid, userid, privtype, threshold, period)
+                VALUES ($1, $2, $3, $4, (interval '1s') * $5)",
+                &[&roomid, &userid, &(privtype as i32), &threshold,
+                  &(period as f64)]).unwrap();
+        trans.commit().unwrap();
+    }
+
+    fn clear_privilege(&self, roomid: i32, userid: Option<i32>,
+            privtype: PrivType) -> bool {
+        self.conn().execute("
+                DELETE FROM privileges
+                WHERE roomid = $1
+                  AND userid IS NOT DISTINCT FROM $2
+                  AND privtype = $3",
+                &[&roomid, &userid, &(privtype as i32)]).unwrap() > 0
+    }
+
+    fn list_privileges(&self, roomid: i32) -> Vec<Privilege> {
+        self.conn().query("
+                SELECT roomid, userid, privtype, threshold,
+                  EXTRACT(EPOCH FROM period)::REAL
+                FROM privileges
+                WHERE roomid = $1
+                ORDER BY userid NULLS FIRST, privtype", &[&roomid]).unwrap()
+            .iter().filter_map(privilege_from_row).collect()
+    }
+
+    fn get_role(&self, roomid: i32, userid: i32) -> Option<Role> {
+        self.conn().query("
+                SELECT role FROM members
+                WHERE roomid = $1 AND userid = $2", &[&roomid, &userid])
+            .unwrap().iter().next().and_then(|row| Role::from_i32(row.get(0)))
+    }
+
+    fn set_role(&self, roomid: i32, userid: i32, role: Role) {
+        self.conn().execute("
+                INSERT INTO members (roomid, userid, role)
+                VALUES ($1, $2, $3)
+                ON CONFLICT (roomid, userid) DO UPDATE SET role = $3",
+                &[&roomid, &userid, &(role as i32)]).unwrap();
+    }
+
+    fn list_members(&self, roomid: i32) -> Vec<(i32, Role)> {
+        self.conn().query("
+                SELECT userid, role FROM members
+                WHERE roomid = $1
+                ORDER BY userid", &[&roomid]).unwrap()
+            .iter().filter_map(|row| Role::from_i32(row.get(1))
+                .map(|role| (row.get(0), role))).collect()
+    }
+
+    fn insert_message(&self, message: &Message) -> i32 {
+        self.conn().query("
+                INSERT INTO messages (roomid, userid, replyid, text, tstamp)
+                VALUES ($1, $2, $3, $4, $5)
+                RETURNING id",
+                &[&message.roomid, &message.userid, &message.replyid,
+                  &message.text, &message.timestamp]).unwrap()
+            .get(0).get(0)
+    }
+
+    fn get_message(&self, messageid: i32) -> Option<Message> {
+        self.conn().query("
+                SELECT id, roomid, userid, replyid, text, tstamp
+                FROM messages
+                WHERE id = $1", &[&messageid]).unwrap()
+            .iter().next().map(message_from_row)
+    }
+
+    fn edit_message(&self, messageid: i32, replyid: Option<i32>, text: &str,
+            timestamp: Timespec) {
+        let conn = self.conn();
+        let trans = conn.transaction().unwrap();
+        trans.execute("
+                INSERT INTO history (messageid, replyid, text, tstamp)
+                SELECT id, replyid, text, $2::TIMESTAMP
+                FROM messages
+                WHERE id = $1", &[&messageid, &timestamp]).unwrap();
+        trans.execute("
+                UPDATE messages
+                SET replyid = $2, text = $3
+                WHERE id = $1", &[&messageid, &replyid, &text]).unwrap();
+        trans.commit().unwrap();
+    }
+
+    fn recent_messages(&self, roomid: i32, limit: i64) -> Vec<Message> {
+        self.conn().query("
+                SELECT * FROM (
+                  SELECT id, roomid, userid, replyid, text, tstamp
+                  FROM messages
+                  WHERE roomid = $1
+                  ORDER BY id DESC
+                  LIMIT $2
+                ) AS _
+                ORDER BY id ASC", &[&roomid, &limit]).unwrap()
+            .iter().map(message_from_row).collect()
+    }
+
+    fn messages_before(&self, roomid: i32, messageid: i32, limit: i64)
+            -> Vec<Message> {
+        self.conn().query("
+                SELECT * FROM (
+                  SELECT id, roomid, userid, replyid, text, tstamp
+                  FROM messages
+                  WHERE roomid = $1 AND id < $2
+                  ORDER BY id DESC
+                  LIMIT $3
+                ) AS _
+                ORDER BY id ASC", &[&roomid, &messageid, &limit]).unwrap()
+            .iter().map(message_from_row).collect()
+    }
+
+    fn messages_after(&self, roomid: i32, messageid: i32, limit: i64)
+            -> Vec<Message> {
+        self.conn().query("
+                SELECT id, roomid, userid, replyid, text, tstamp
+                FROM messages
+                WHERE roomid = $1 AND id > $2
+                ORDER BY id ASC
+                LIMIT $3", &[&roomid, &messageid, &limit]).unwrap()
+            .iter().map(message_from_row).collect()
+    }
+
+    fn replies(&self, messageid: i32, roomid: i32) -> Vec<Message> {
+        self.conn().query("
+                SELECT id, roomid, userid, replyid, text, tstamp
+                FROM messages
+                WHERE replyid = $1 AND roomid = $2 AND text <> ''
+                ORDER BY id ASC", &[&messageid, &roomid]).unwrap()
+            .iter().map(message_from_row).collect()
+    }
+
+    fn count_replies(&self, messageid: i32, roomid: i32) -> i64 {
+        self.conn().query("
+                SELECT COUNT(*) FROM messages
+                WHERE replyid = $1 AND roomid = $2 AND text <> ''",
+                &[&messageid, &roomid]).unwrap()
+            .get(0).get(0)
+    }
+
+    fn move_messages(&self, messageids: &[i32], roomid: i32) {
+        self.conn().execute("
+                UPDATE messages SET roomid = $2
+                WHERE id = ANY($1)", &[&messageids, &roomid]).unwrap();
+    }
+
+    fn history(&self, messageid: i32) -> Vec<Revision> {
+        self.conn().query("
+                SELECT messageid, replyid, text, tstamp
+                FROM history
+                WHERE messageid = $1
+                ORDER BY id", &[&messageid]).unwrap()
+            .iter().map(|row| Revision {
+                messageid: row.get(0),
+                replyid: row.get(1),
+                text: row.get(2),
+                timestamp: row.get(3)
+            }).collect()
+    }
+
+    fn search(&self, query: &SearchQuery) -> Vec<SearchResult> {
+        self.conn().query("
+                SELECT id, roomid, userid, replyid, text, tstamp,
+                  ts_headline('pg_catalog.english',
+                    translate(text, chr(2) || chr(3), ''), tsquery,
+                    'StartSel=' || chr(2) || ', StopSel=' || chr(3)),
+                  ts_rank(textsearch, tsquery)
+                FROM messages,
+                  plainto_tsquery('pg_catalog.english', $1) AS tsquery
+                WHERE textsearch @@ tsquery
+                  AND roomid = ANY($2)
+                  AND ($3::INT IS NULL OR userid = $3)
+                  AND ($4::TIMESTAMP IS NULL OR tstamp >= $4)
+                  AND ($5::TIMESTAMP IS NULL OR tstamp < $5)
+                  AND ($6::BOOL IS NULL OR (replyid IS NOT NULL) = $6)
+                ORDER BY 8 DESC, id DESC
+                LIMIT $7", &[&query.text, &query.roomids, &query.userid,
+                    &query.since, &query.until, &query.hasreply,
+                    &query.limit]).unwrap()
+            .iter().map(|row| {
+                let snippet = mark(&row.get::<usize, String>(6));
+                let rank = row.get(7);
+                SearchResult {
+                    message: message_from_row(row),
+                    snippet,
+                    rank
+                }
+            }).collect()
+    }
+
+    fn add_notification(&self, userid: i32, messageid: i32, kind: NotifyKind,
+            timestamp: Timespec) -> Option<i32> {
+        self.conn().query("
+                INSERT INTO notifications (userid, messageid, kind, tstamp)
+                VALUES ($1, $2, $3, $4)
+                ON CONFLICT (userid, messageid, kind) DO NOTHING
+                RETURNING id",
+                &[&userid, &messageid, &notifykind_to_int(kind), &timestamp])
+            .unwrap().iter().next().map(|row| row.get(0))
+    }
+
+    fn unread_notifications(&self, userid: i32, limit: i64)
+            -> Vec<Notification> {
+        self.conn().query("
+                SELECT id, userid, messageid, kind, tstamp, read
+                FROM notifications
+                WHERE userid = $1 AND NOT read
+                ORDER BY id DESC
+                LIMIT $2", &[&userid, &limit]).unwrap()
+            .iter().map(notification_from_row).collect()
+    }
+
+    fn mark_notifications_read(&self, userid: i32, ids: Option<&[i32]>) {
+        self.conn().execute("
+                UPDATE notifications SET read = TRUE
+                WHERE userid = $1 AND ($2::INT[] IS NULL OR id = ANY($2))",
+                &[&userid, &ids]).unwrap();
+    }
+
+    fn get_read_marker(&self, userid: i32, roomid: i32) -> Option<i32> {
+        self.conn().query("
+                SELECT messageid FROM readmarkers
+                WHERE userid = $1 AND roomid = $2", &[&userid, &roomid])
+            .unwrap().iter().next().map(|row| row.get(0))
+    }
+
+    fn set_read_marker(&self, userid: i32, roomid: i32, messageid: i32) {
+        self.conn().execute("
+                INSERT INTO readmarkers (userid, roomid, messageid)
+                VALUES ($1, $2, $3)
+                ON CONFLICT (userid, roomid) DO UPDATE
+                SET messageid = GREATEST(readmarkers.messageid, $3)",
+                &[&userid, &roomid, &messageid]).unwrap();
+    }
+
+    fn count_unread(&self, userid: i32, roomid: i32) -> i64 {
+        self.conn().query("
+                SELECT COUNT(*) FROM messages
+                WHERE roomid = $2
+                  AND userid NOT IN ($1, -1)
+                  AND text <> ''
+                  AND id > COALESCE((SELECT messageid FROM readmarkers
+                                     WHERE userid = $1 AND roomid = $2), 0)",
+                &[&userid, &roomid]).unwrap()
+            .get(0).get(0)
+    }
+
+    fn find_vote(&self, messageid: i32, userid: i32, votetype: &VoteType)
+            -> Option<i32> {
+        self.conn().query("
+                SELECT id FROM votes
+                WHERE messageid = $1 AND userid = $2 AND votetype = $3",
+                &[&messageid, &userid, &votetype_to_int(votetype)]).unwrap()
+            .iter().next().map(|row| row.get(0))
+    }
+
+    fn insert_vote(&self, vote: &Vote) -> i32 {
+        self.conn().query("
+                INSERT INTO votes (messageid, userid, votetype, tstamp)
+                VALUES ($1, $2, $3, $4)
+                RETURNING id",
+                &[&vote.messageid, &vote.userid,
+                  &votetype_to_int(&vote.votetype), &vote.timestamp]).unwrap()
+            .get(0).get(0)
+    }
+
+    fn delete_vote(&self, voteid: i32) {
+        self.conn().execute("DELETE FROM votes WHERE id = $1", &[&voteid])
+            .unwrap();
+    }
+
+    fn message_votes(&self, messageid: i32) -> Vec<Vote> {
+        self.conn().query("
+                SELECT id, messageid, userid, votetype, tstamp
+                FROM votes
+                WHERE messageid = $1", &[&messageid]).unwrap()
+            .iter().map(vote_from_row).collect()
+    }
+
+    fn starboard(&self, roomid: i32, votetype: &VoteType)
+            -> Vec<StarredMessage> {
+        self.conn().query(match *votetype {
+            VoteType::Star =>
+                "SELECT m.id, m.text, m.tstamp,
+                       MIN(u.id), MIN(u.username),
+                       COUNT(v.userid)
+                FROM votes v
+                  INNER JOIN messages m ON v.messageid = m.id
+                  LEFT JOIN users u ON m.userid = u.id
+                WHERE v.votetype = 3 AND m.roomid = $1
+                GROUP BY m.id
+                ORDER BY (COUNT(v.userid) * POW(
+                  EXTRACT(EPOCH FROM (NOW() - m.tstamp)) / 60,
+                  -1.5)) DESC
+                LIMIT 10",
+            VoteType::Pin =>
+                "SELECT m.id, m.text, m.tstamp,
+                       MIN(u.id), MIN(u.username),
+                       COUNT(v.userid)
+                FROM votes v
+                  INNER JOIN messages m ON v.messageid = m.id
+                  LEFT JOIN users u ON m.userid = u.id
+                WHERE v.votetype = 4 AND m.roomid = $1
+                GROUP BY m.id
+                ORDER BY COUNT(v.userid)",
+            _ => panic!("weird votetype in starboard")
+        }, &[&roomid]).unwrap().iter().map(|row| StarredMessage {
+            id: row.get(0),
+            text: row.get(1),
+            timestamp: row.get(2),
+            userid: row.get(3),
+            username: row.get(4),
+            votecount: row.get(5)
+        }).collect()
+    }
+
+    fn count_messages(&self, roomid: i32, userid: i32, since: Timespec) -> i64 {
+        self.conn().query("
+                SELECT COUNT(*)
+                FROM messages
+                WHERE roomid = $1
+                  AND userid = $2
+                  AND tstamp >= $3", &[&roomid, &userid, &since]).unwrap()
+            .get(0).get(0)
+    }
+
+    fn count_edits(&self, roomid: i32, userid: i32, since: Timespec) -> i64 {
+        self.conn().query("
+                SELECT COUNT(*)
+                FROM history h
+                INNER JOIN messages m ON m.id = h.messageid
+                WHERE m.roomid = $1
+                  AND m.userid = $2
+                  AND m.text != ''
+                  AND h.tstamp >= $3", &[&roomid, &userid, &since]).unwrap()
+            .get(0).get(0)
+    }
+
+    fn count_deletes(&self, roomid: i32, userid: i32, since: Timespec) -> i64 {
+        self.conn().query("
+                SELECT COUNT(DISTINCT h.messageid)
+                FROM history h
+                INNER JOIN messages m ON m.id = h.messageid
+                WHERE m.roomid = $1
+                  AND m.userid = $2
+                  AND m.text = ''
+                  AND h.tstamp >= $3", &[&roomid, &userid, &since]).unwrap()
+            .get(0).get(0)
+    }
+
+    fn count_votes(&self, roomid: i32, userid: i32, votetype: &VoteType,
+            since: Timespec) -> i64 {
+        self.conn().query("
+                SELECT COUNT(*)
+                FROM votes v
+                INNER JOIN messages m ON m.id = v.messageid
+                WHERE m.roomid = $1
+                  AND v.userid = $2
+                  AND v.votetype = $3
+                  AND v.tstamp >= $4",
+                &[&roomid, &userid, &votetype_to_int(votetype), &since])
+            .unwrap().get(0).get(0)
+    }
+
+}
@@ -0,0 +1,94 @@
+use time::{Duration, Timespec};
+
+use std::cmp;
+use std::collections::HashMap;
+use std::sync::Mutex;
+
+// counts failed logins per key (an ip address, a username): the first `free`
+// in a row cost nothing, and after that each one doubles how long the key
+// has to wait before trying again, up to `lockout` seconds
+//
+// a key that goes `lockout` seconds without failing is forgotten
+pub struct Backoff {
+    failures: Mutex<HashMap<String, Failures>>
+}
+
+struct Failures {
+    count: u32,
+    last: Timespec,
+    until: Timespec
+}
+
+impl Backoff {
+
+    pub fn new() -> Backoff {
+        Backoff { failures: Mutex::new(HashMap::new()) }
+    }
+
+    // how many seconds the key still has to wait, if any
+    pub fn wait(&self, key: &str, now: Timespec) -> Option<i64> {
+        self.failures.lock().unwrap().get(key)
+            .and_then(|failures| if failures.until > now {
+                Some(cmp::max(failures.until.sec - now.sec, 1))
+            } else { None })
+    }
+
+    pub fn fail(&self, key: &str, now: Timespec, free: u32, lockout: i64) {
+        let lockout = Duration::seconds(lockout);
+        let mut failures = self.failures.lock().unwrap();
+        failures.retain(|_, entry| now - entry.last <= lockout);
+
+        let entry = failures.entry(key.to_string()).or_insert(Failures {
+            count: 0,
+            last: now,
+            until: now
+        });
+        entry.count += 1;
+        entry.last = now;
+        if entry.count > free {
+            let shift = cmp::min(entry.count - free - 1, 30);
+            entry.until = now +
+                cmp::min(Duration::seconds(1 << shift), lockout);
+        }
+    }
+
+    pub fn clear(&self, key: &str) {
+        self.failures.lock().unwrap().remove(key);
+    }
+
+}
+
+// remembers when each key did something, so that it can be held to `limit`
+// times per `period` seconds
+pub struct Window {
+    events: Mutex<HashMap<String, Vec<Timespec>>>
+}
+
+impl Window {
+
+    pub fn new() -> Window {
+        Window { events: Mutex::new(HashMap::new()) }
+    }
+
+    // records another event for the key, unless it's already used up its
+    // limit, in which case this returns how many seconds until it hasn't
+    pub fn hit(&self, key: &str, now: Timespec, limit: u32, period: i64)
+            -> Result<(), i64> {
+        let period = Duration::seconds(period);
+        let mut events = self.events.lock().unwrap();
+        for times in events.values_mut() {
+            times.retain(|&time| now - time < period);
+        }
+        events.retain(|_, times| !times.is_empty());
+
+        let times = events.entry(key.to_string()).or_default();
+        if times.len() >= limit as usize {
+            // limit could be 0, in which case there's nothing to wait for
+            return Err(times.first().map_or(period.num_seconds(), |&first|
+                cmp::max((first + period).sec - now.sec, 1)));
+        }
+        times.push(now);
+        Ok(())
+    }
+
+}
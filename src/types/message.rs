@@ -10,3 +10,12 @@ pub struct Message {
     pub text: String,
     pub timestamp: Timespec
 }
+
+// a previous version of a message, saved whenever it gets edited or deleted
+#[derive(Clone)]
+pub struct Revision {
+    pub messageid: i32,
+    pub replyid: Option<i32>,
+    pub text: String,
+    pub timestamp: Timespec
+}
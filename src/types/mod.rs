@@ -1,2 +1,8 @@
 pub mod message;
 pub mod vote;
+pub mod user;
+pub mod room;
+pub mod privilege;
+pub mod notification;
+pub mod session;
+pub mod search;
@@ -0,0 +1,43 @@
+extern crate time;
+use time::Timespec;
+
+// something in a message that concerns a user other than its author
+#[derive(Clone)]
+pub struct Notification {
+    pub id: i32,
+    pub userid: i32,
+    pub messageid: i32,
+    pub kind: NotifyKind,
+    pub timestamp: Timespec,
+    pub read: bool
+}
+
+#[derive(Copy, Clone, PartialEq)]
+pub enum NotifyKind {
+    // the message has an @username in it
+    Mention,
+    // the message replies to one of theirs
+    Reply
+}
+
+pub fn notifykind_to_int(kind: NotifyKind) -> i32 {
+    match kind {
+        NotifyKind::Mention => 0,
+        NotifyKind::Reply => 1
+    }
+}
+
+pub fn int_to_notifykind(kind: i32) -> Option<NotifyKind> {
+    match kind {
+        0 => Some(NotifyKind::Mention),
+        1 => Some(NotifyKind::Reply),
+        _ => None
+    }
+}
+
+pub fn notifykind_name(kind: NotifyKind) -> &'static str {
+    match kind {
+        NotifyKind::Mention => "mention",
+        NotifyKind::Reply => "reply"
+    }
+}
@@ -0,0 +1,13 @@
+use enums::privtype::*;
+
+// one row of the privileges table; a userid of None applies to everyone in
+// the room who doesn't have a row of their own
+#[derive(Clone)]
+pub struct Privilege {
+    pub roomid: i32,
+    pub userid: Option<i32>,
+    pub privtype: PrivType,
+    pub threshold: i32,
+    // in seconds
+    pub period: i32
+}
@@ -0,0 +1,15 @@
+use enums::visibility::*;
+
+#[derive(Clone)]
+pub struct Room {
+    pub id: i32,
+    pub name: String,
+    pub description: String,
+    pub topic: String,
+    // archived rooms can still be read, but nothing in them can change
+    pub archived: bool,
+    pub visibility: Visibility,
+    // a direct message conversation: invite-only, nameless, and its members
+    // are exactly the participants
+    pub direct: bool
+}
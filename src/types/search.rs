@@ -0,0 +1,25 @@
+extern crate time;
+use time::Timespec;
+
+use types::message::*;
+
+// what to look for; `roomids` is every room the search may touch, which the
+// server has already narrowed down to the ones the user can read
+pub struct SearchQuery {
+    pub text: String,
+    pub roomids: Vec<i32>,
+    pub userid: Option<i32>,
+    pub since: Option<Timespec>,
+    pub until: Option<Timespec>,
+    // Some(true) for only replies, Some(false) for only non-replies
+    pub hasreply: Option<bool>,
+    pub limit: i64
+}
+
+// a matching message, with the matched words wrapped in <mark></mark> (and
+// the rest of the text escaped, so that's the only markup in it)
+pub struct SearchResult {
+    pub message: Message,
+    pub snippet: String,
+    pub rank: f32
+}
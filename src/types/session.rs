@@ -0,0 +1,22 @@
+extern crate time;
+use time::Timespec;
+
+// one login on one device; the token that goes with it never leaves the store
+// except when the session is created
+#[derive(Clone)]
+pub struct Session {
+    pub id: i32,
+    pub userid: i32,
+    // whatever the client called itself when logging in
+    pub label: String,
+    pub created: Timespec,
+    pub lastused: Timespec,
+    pub expires: Timespec,
+    pub revoked: bool
+}
+
+impl Session {
+    pub fn expired(&self, now: Timespec) -> bool {
+        self.expires <= now
+    }
+}
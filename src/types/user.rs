@@ -0,0 +1,8 @@
+#[derive(Clone)]
+pub struct User {
+    pub id: i32,
+    pub username: String,
+    // a PHC string, see password.rs
+    pub password: String,
+    pub theme: String
+}
@@ -10,15 +10,26 @@ pub struct Vote {
     pub timestamp: Timespec
 }
 
+// a message on the starboard/pinboard, along with how many votes put it there
 #[derive(Clone)]
+pub struct StarredMessage {
+    pub id: i32,
+    pub text: String,
+    pub timestamp: Timespec,
+    pub userid: Option<i32>,
+    pub username: Option<String>,
+    pub votecount: i64
+}
+
+#[derive(Clone, PartialEq)]
 pub enum VoteType {
     Upvote, Downvote, Star, Pin
 }
 
 pub fn votetype_to_int(votetype: &VoteType) -> i32 {
-    match votetype {
-        &VoteType::Upvote => 1, &VoteType::Downvote => 2,
-        &VoteType::Star => 3,   &VoteType::Pin => 4
+    match *votetype {
+        VoteType::Upvote => 1, VoteType::Downvote => 2,
+        VoteType::Star => 3,   VoteType::Pin => 4
     }
 }
 
@@ -1,33 +1,32 @@
-use serde_json::{Value, Map};
+use rand::{Rng, OsRng};
+use rand::distributions::Alphanumeric;
 
-extern crate crypto;
-use self::crypto::bcrypt;
-
-use std::io::Write;
-
-pub fn get_string(json: &Map<String, Value>, key: &str) -> Option<String> {
-    match json.get(key) {
-        Some(&Value::String(ref s)) => Some(s.clone()),
-        _ => None
-    }
+// for auth tokens, invite links and the like
+pub fn random_token() -> String {
+    let mut rng = OsRng::new().unwrap();
+    rng.sample_iter(&Alphanumeric).take(32).collect()
 }
 
-pub fn get_i32(json: &Map<String, Value>, key: &str) -> Option<i32> {
-    match json.get(key) {
-        Some(&Value::Number(ref i)) => i.as_i64().map(|x| x as i32),
-        _ => None
+// the usernames @mentioned in a message, each once, in order of appearance
+pub fn mentions(text: &str) -> Vec<String> {
+    let mut mentions: Vec<String> = Vec::new();
+    for (i, _) in text.match_indices('@') {
+        // an @ in the middle of a word (like an email address) isn't one
+        if text[..i].chars().next_back()
+                .is_some_and(|c| c.is_alphanumeric()) {
+            continue;
+        }
+        let username: String = text[i + 1..].chars()
+            .take_while(|&c| c.is_alphanumeric() || c == '_' || c == '-')
+            .collect();
+        if !username.is_empty() && !mentions.contains(&username) {
+            mentions.push(username);
+        }
     }
+    mentions
 }
 
-// adapted from https://www.reddit.com/r/rust/comments/2sipzj/is_there_an_easy_way_to_hash_passwords_in_rust/cnptvs6
-pub fn hash_pwd(salt: [u8; 16], password: &String) -> Vec<u8> {
-    let mut result = [0u8; 24];
-    let password: &[u8] = &password.as_bytes().into_iter().cloned().take(72)
-        .collect::<Vec<u8>>()[..];
-    let empty_password = &[0];
-    bcrypt::bcrypt(10, &salt, if password.is_empty() { empty_password }
-                   else { password }, &mut result);
-    let mut v = Vec::with_capacity(24);
-    v.write(&result).unwrap();
-    v
+// for putting user text into markup that clients will render
+pub fn escape_html(text: &str) -> String {
+    text.replace('&', "&amp;").replace('<', "&lt;").replace('>', "&gt;")
 }
@@ -0,0 +1,177 @@
+// shared harness for the protocol tests: spins up an in-memory server on an
+// ephemeral port and drives it with scripted websocket clients
+
+#![allow(dead_code)]
+
+use glavra::Glavra;
+use glavra::config::{Config, RateLimit, StoreKind};
+use glavra::enums::privtype::PrivType;
+
+use serde_json;
+use serde_json::Value;
+
+use ws;
+
+use std::net::SocketAddr;
+use std::sync::Arc;
+use std::sync::mpsc;
+use std::thread;
+use std::time::Duration;
+
+const TIMEOUT_MS: u64 = 5000;
+
+pub fn config() -> Config {
+    Config {
+        address: String::from("127.0.0.1:0"),
+        store: StoreKind::Memory,
+        // the real cost would make every register and auth take a while
+        argon2_memory: 64,
+        argon2_iterations: 1,
+        // and every test registers everyone from the same address
+        register_limit: 1000,
+        ..Config::default()
+    }
+}
+
+// changes the privilege every room in `config` starts out with
+pub fn set_limit(config: &mut Config, privtype: PrivType, threshold: i32,
+                 period: i32) {
+    config.ratelimits.retain(|&(p, _)| p != privtype);
+    config.ratelimits.push((privtype, RateLimit {
+        threshold,
+        period
+    }));
+}
+
+pub fn server() -> SocketAddr {
+    server_with(config())
+}
+
+pub fn server_with(config: Config) -> SocketAddr {
+    Glavra::in_memory(config).spawn().unwrap()
+}
+
+// for tests that also need to poke the server directly
+pub fn shared_server() -> (SocketAddr, Arc<Glavra>) {
+    let glavra = Arc::new(Glavra::in_memory(config()));
+    (Glavra::spawn_shared(glavra.clone()).unwrap(), glavra)
+}
+
+struct Recorder {
+    out: ws::Sender,
+    opened: mpsc::Sender<ws::Sender>,
+    frames: mpsc::Sender<Value>
+}
+
+impl ws::Handler for Recorder {
+    fn on_open(&mut self, _: ws::Handshake) -> ws::Result<()> {
+        self.opened.send(self.out.clone()).unwrap();
+        Ok(())
+    }
+
+    fn on_message(&mut self, msg: ws::Message) -> ws::Result<()> {
+        let frame = serde_json::from_str(&msg.into_text().unwrap()).unwrap();
+        self.frames.send(frame).unwrap();
+        Ok(())
+    }
+}
+
+pub struct Client {
+    out: ws::Sender,
+    frames: mpsc::Receiver<Value>
+}
+
+impl Client {
+
+    // `query` is everything after the `?`, e.g. "room=1"
+    pub fn connect(addr: SocketAddr, query: &str) -> Client {
+        let url = format!("ws://{}/?{}", addr, query);
+        let (opened_tx, opened_rx) = mpsc::channel();
+        let (frames_tx, frames_rx) = mpsc::channel();
+        thread::spawn(move || {
+            ws::connect(url, |out| Recorder {
+                out,
+                opened: opened_tx.clone(),
+                frames: frames_tx.clone()
+            }).unwrap();
+        });
+        Client {
+            out: opened_rx.recv_timeout(Duration::from_millis(TIMEOUT_MS))
+                .expect("timed out connecting"),
+            frames: frames_rx
+        }
+    }
+
+    pub fn send(&self, frame: Value) {
+        self.out.send(serde_json::to_string(&frame).unwrap()).unwrap();
+    }
+
+    pub fn send_raw(&self, text: &str) {
+        self.out.send(text).unwrap();
+    }
+
+    pub fn close(&self) {
+        self.out.close(ws::CloseCode::Normal).unwrap();
+    }
+
+    // the very next frame, whatever it is
+    pub fn recv(&self) -> Value {
+        self.frames.recv_timeout(Duration::from_millis(TIMEOUT_MS))
+            .expect("timed out waiting for a frame")
+    }
+
+    // the next frame of the given type, skipping anything else (starboard
+    // updates and the like)
+    pub fn expect(&self, kind: &str) -> Value {
+        loop {
+            let frame = self.recv();
+            if frame["type"] == kind {
+                return frame;
+            }
+        }
+    }
+
+    pub fn expect_error(&self) -> i64 {
+        self.expect("error")["code"].as_i64().unwrap()
+    }
+
+    // asserts that nothing but the given frame types shows up for a bit
+    pub fn expect_none(&self, kind: &str) {
+        while let Ok(frame) = self.frames.recv_timeout(
+                Duration::from_millis(300)) {
+            assert!(frame["type"] != kind, "unexpected frame {}", frame);
+        }
+    }
+
+    pub fn register(&self, username: &str, password: &str) -> Value {
+        self.send(json!({
+            "type": "register",
+            "username": username,
+            "password": password
+        }));
+        self.expect("register")
+    }
+
+    pub fn auth(&self, username: &str, password: &str) -> Value {
+        self.send(json!({
+            "type": "auth",
+            "username": username,
+            "password": password
+        }));
+        self.expect("auth")
+    }
+
+    pub fn say(&self, text: &str) {
+        self.send(json!({ "type": "message", "text": text }));
+    }
+
+}
+
+// a logged-in client that has joined a room and read past the join replay
+pub fn join(addr: SocketAddr, room: i32, username: &str) -> Client {
+    let client = Client::connect(addr, &format!("room={}", room));
+    client.expect("roominfo");
+    // (its own presence frame goes by before the register reply)
+    assert_eq!(client.register(username, "hunter2")["success"], true);
+    client
+}
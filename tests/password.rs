@@ -0,0 +1,59 @@
+extern crate glavra;
+
+use glavra::config::Config;
+use glavra::password;
+
+// the published cost 10 bcrypt vector for the empty password...
+const OPENBSD: &str =
+    "$2a$10$k1wbIrmNyFAPwPVPSVa/zecw2BCEnBwVS2GbrmgzxFUOqW9dk4TCW";
+// ...written out the way migration 0010 turned what the old hash_pwd stored:
+// all 24 bytes of output, where the $2a$ string only keeps 23
+const LEGACY: &str =
+    "$bcrypt$c=10$m3ydKtoP0HCRyRXRUXcB1g$ey4DEGpDyXU4Idtoi1zHWQsY/fm6VEYy";
+
+fn config() -> Config {
+    Config {
+        argon2_memory: 64,
+        argon2_iterations: 1,
+        ..Config::default()
+    }
+}
+
+#[test]
+fn legacy_bcrypt_hashes_verify() {
+    assert!(password::verify("", LEGACY));
+    assert!(!password::verify("x", LEGACY));
+    assert!(!password::verify("", &LEGACY.replace("c=10", "c=11")));
+    assert!(!password::verify("", &LEGACY[..LEGACY.len() - 1]));
+    // only the format the migration wrote is understood
+    assert!(!password::verify("", OPENBSD));
+}
+
+#[test]
+fn argon2_hashes_verify() {
+    let hash = password::hash("hunter2", &config()).unwrap();
+    assert!(hash.starts_with("$argon2id$v=19$m=64,t=1,p="));
+    assert!(password::verify("hunter2", &hash));
+    assert!(!password::verify("hunter3", &hash));
+    // every hash gets its own salt
+    assert!(hash != password::hash("hunter2", &config()).unwrap());
+}
+
+#[test]
+fn bad_settings_fail_to_hash() {
+    // argon2 wants at least 8 KiB per lane
+    let tiny = Config { argon2_memory: 1, ..config() };
+    assert!(password::hash("hunter2", &tiny).is_none());
+}
+
+#[test]
+fn rehash_when_settings_change() {
+    let hash = password::hash("hunter2", &config()).unwrap();
+    assert!(!password::needs_rehash(&hash, &config()));
+    assert!(password::needs_rehash(LEGACY, &config()));
+
+    let costlier = Config { argon2_iterations: 2, ..config() };
+    assert!(password::needs_rehash(&hash, &costlier));
+    let bigger = Config { argon2_memory: 128, ..config() };
+    assert!(password::needs_rehash(&hash, &bigger));
+}
@@ -0,0 +1,1113 @@
+extern crate glavra;
+#[macro_use]
+extern crate serde_json;
+extern crate ws;
+
+mod common;
+use common::*;
+
+use glavra::enums::privtype::PrivType;
+use glavra::protocol::Request;
+
+use std::thread;
+use std::time::Duration;
+
+// error codes, in the order ErrCode declares them
+const NEED_LOGIN: i64 = 0;
+const MALFORMED: i64 = 1;
+const EMPTY_MSG: i64 = 2;
+const EDIT_DELETED: i64 = 3;
+const NO_ROOM_ID: i64 = 5;
+const INVALID_ROOM_ID: i64 = 6;
+const ROOM_NOT_EXIST: i64 = 7;
+const USERNAME_TOO_LONG: i64 = 8;
+const RATE_LIMIT: i64 = 9;
+const USER_NOT_EXIST: i64 = 11;
+const MESSAGE_NOT_EXIST: i64 = 12;
+const NO_PRIVILEGE: i64 = 13;
+const ROOM_ARCHIVED: i64 = 14;
+const NOT_MEMBER: i64 = 15;
+const INVALID_REPLY: i64 = 16;
+const NOT_JOINED: i64 = 17;
+const BAD_TOKEN: i64 = 18;
+const TOKEN_EXPIRED: i64 = 19;
+const SESSION_NOT_EXIST: i64 = 20;
+const LOGIN_THROTTLED: i64 = 21;
+const REGISTER_THROTTLED: i64 = 22;
+const PASSWORD_TOO_LONG: i64 = 24;
+const HASH_FAILED: i64 = 25;
+
+#[test]
+fn register_and_auth() {
+    let addr = server();
+    let client = Client::connect(addr, "");
+
+    let registered = client.register("alice", "hunter2");
+    assert_eq!(registered["success"], true);
+    assert!(registered["token"].is_string());
+    let userid = registered["userid"].clone();
+
+    assert_eq!(client.register("alice", "hunter3")["success"], false);
+
+    client.send(json!({
+        "type": "register",
+        "username": "a_very_long_username_indeed",
+        "password": "x"
+    }));
+    assert_eq!(client.expect_error(), USERNAME_TOO_LONG);
+    // turned away before it's hashed
+    let long = "x".repeat(2000);
+    client.send(json!({ "type": "register", "username": "bob",
+        "password": long }));
+    assert_eq!(client.expect_error(), PASSWORD_TOO_LONG);
+    client.send(json!({ "type": "auth", "username": "alice",
+        "password": long }));
+    assert_eq!(client.expect_error(), PASSWORD_TOO_LONG);
+
+    let authed = client.auth("alice", "hunter2");
+    assert_eq!(authed["success"], true);
+    assert_eq!(authed["userid"], userid);
+    // every login is its own session
+    assert!(authed["token"].is_string());
+    assert!(authed["token"] != registered["token"]);
+
+    assert_eq!(client.auth("alice", "wrong")["success"], false);
+    assert_eq!(client.auth("nobody", "hunter2")["success"], false);
+}
+
+#[test]
+fn long_passwords_are_not_truncated() {
+    let addr = server();
+    let password = "x".repeat(80);
+    let client = Client::connect(addr, "");
+    assert_eq!(client.register("alice", &password)["success"], true);
+
+    let almost = format!("{}y", "x".repeat(79));
+    assert_eq!(Client::connect(addr, "").auth("alice", &almost)["success"],
+        false);
+    assert_eq!(Client::connect(addr, "").auth("alice", &password)["success"],
+        true);
+    assert_eq!(Client::connect(addr, "").auth("nobody", &password)["success"],
+        false);
+}
+
+#[test]
+fn token_login() {
+    let addr = server();
+    let token = Client::connect(addr, "").register("bob", "hunter2")["token"]
+        .as_str().unwrap().to_string();
+
+    let client = Client::connect(addr, &format!("token={}", token));
+    let auth = client.recv();
+    assert_eq!(auth["type"], "auth");
+    assert_eq!(auth["username"], "bob");
+    assert_eq!(client.recv()["type"], "preferences");
+}
+
+#[test]
+fn messages_are_scoped_to_rooms() {
+    let addr = server();
+    let lobby = Client::connect(addr, "");
+    lobby.register("carol", "hunter2");
+    lobby.send(json!({ "type": "room", "name": "other", "desc": "" }));
+    let other = lobby.expect("room")["id"].as_i64().unwrap() as i32;
+
+    let alice = join(addr, 1, "alice");
+    let bob = join(addr, 1, "bob");
+    let carol = join(addr, other, "carol2");
+    alice.expect("presence");
+
+    alice.say("hello");
+    let message = bob.expect("message");
+    assert_eq!(message["text"], "hello");
+    assert_eq!(message["username"], "alice");
+    assert_eq!(alice.expect("message")["text"], "hello");
+    carol.expect_none("message");
+
+    // the new message is part of the replay for anyone joining later
+    let late = Client::connect(addr, "room=1");
+    assert_eq!(late.recv()["type"], "roominfo");
+    assert_eq!(late.expect("message")["text"], "hello");
+}
+
+#[test]
+fn edit_delete_and_history() {
+    let addr = server();
+    let alice = join(addr, 1, "alice");
+
+    alice.say("frist");
+    let id = alice.expect("message")["id"].clone();
+
+    alice.send(json!({ "type": "edit", "id": id, "text": "first" }));
+    let edit = alice.expect("edit");
+    assert_eq!(edit["id"], id);
+    assert_eq!(edit["text"], "first");
+
+    alice.send(json!({ "type": "delete", "id": id }));
+    assert_eq!(alice.expect("edit")["text"], "");
+
+    alice.send(json!({ "type": "edit", "id": id, "text": "again" }));
+    assert_eq!(alice.expect_error(), EDIT_DELETED);
+
+    alice.send(json!({ "type": "history", "id": id }));
+    let history = alice.expect("history");
+    assert_eq!(history["roomid"], 1);
+    let revisions = history["revisions"].clone();
+    let texts: Vec<_> = revisions.as_array().unwrap().iter()
+        .map(|r| r["text"].clone()).collect();
+    assert_eq!(texts, vec![json!("frist"), json!("first")]);
+}
+
+#[test]
+fn votes_toggle() {
+    let addr = server();
+    let alice = join(addr, 1, "alice");
+    let bob = join(addr, 1, "bob");
+    alice.expect("presence");
+
+    alice.say("vote for me");
+    let id = alice.expect("message")["id"].clone();
+
+    bob.send(json!({ "type": "vote", "messageid": id, "votetype": 1 }));
+    let vote = alice.expect("vote");
+    assert_eq!(vote["messageid"], id);
+    assert_eq!(vote["votetype"], 1);
+
+    bob.send(json!({ "type": "vote", "messageid": id, "votetype": 1 }));
+    assert_eq!(alice.expect("undovote")["messageid"], id);
+
+    bob.send(json!({ "type": "vote", "messageid": id, "votetype": 3 }));
+    alice.expect("vote");
+    let starboard = alice.expect("starboard");
+    assert_eq!(starboard["votetype"], 3);
+    assert_eq!(starboard["messages"][0]["id"], id);
+    assert_eq!(starboard["messages"][0]["votecount"], 1);
+}
+
+#[test]
+fn error_frames() {
+    let addr = server();
+
+    let anon = Client::connect(addr, "room=1");
+    anon.say("hi");
+    assert_eq!(anon.expect_error(), NEED_LOGIN);
+
+    anon.send_raw("this is not json");
+    assert_eq!(anon.expect_error(), MALFORMED);
+    anon.send(json!({ "type": "nonsense" }));
+    assert_eq!(anon.expect_error(), MALFORMED);
+    anon.send(json!({ "type": "message" }));
+    assert_eq!(anon.expect_error(), MALFORMED);
+    anon.send(json!({ "type": "edit", "text": "no id" }));
+    let error = anon.expect("error");
+    assert_eq!(error["code"], MALFORMED);
+    assert!(error["reason"].as_str().unwrap().contains("`id`"));
+
+    let alice = join(addr, 1, "alice");
+    alice.say("");
+    assert_eq!(alice.expect_error(), EMPTY_MSG);
+
+    assert_eq!(Client::connect(addr, "room=abc").expect_error(),
+        INVALID_ROOM_ID);
+    assert_eq!(Client::connect(addr, "room=999").expect_error(),
+        ROOM_NOT_EXIST);
+    assert_eq!(Client::connect(addr, "queryuser=999").expect_error(),
+        USER_NOT_EXIST);
+}
+
+#[test]
+fn send_message_rate_limit() {
+    let addr = server();
+    let alice = join(addr, 1, "alice");
+
+    // the default SendMessage privilege is 5 messages per 5 seconds
+    for i in 0..5 {
+        alice.say(&format!("message {}", i));
+        alice.expect("message");
+    }
+    alice.say("one too many");
+    assert_eq!(alice.expect_error(), RATE_LIMIT);
+}
+
+#[test]
+fn scrollback_pages() {
+    let addr = server();
+    let alice = join(addr, 1, "alice");
+
+    // five messages, staying just inside the SendMessage rate limit
+    let ids: Vec<i64> = (0..5).map(|i| {
+        alice.say(&format!("message {}", i));
+        alice.expect("message")["id"].as_i64().unwrap()
+    }).collect();
+    // upvoting your own messages isn't allowed by default
+    let bob = join(addr, 1, "bob");
+    bob.send(json!({ "type": "vote", "messageid": ids[2], "votetype": 1 }));
+    bob.expect("vote");
+    alice.expect("vote");
+
+    let page = |request: serde_json::Value| -> Vec<i64> {
+        alice.send(request);
+        alice.expect("scrollback")["messages"].as_array().unwrap().iter()
+            .map(|m| m["id"].as_i64().unwrap()).collect()
+    };
+
+    assert_eq!(page(json!({ "type": "scrollback", "limit": 2 })),
+        vec![ids[3], ids[4]]);
+    assert_eq!(page(json!({ "type": "scrollback", "before": ids[4],
+        "limit": 2 })), vec![ids[2], ids[3]]);
+    assert_eq!(page(json!({ "type": "scrollback", "after": ids[1],
+        "limit": 2 })), vec![ids[2], ids[3]]);
+    assert_eq!(page(json!({ "type": "scrollback", "around": ids[2],
+        "limit": 3 })), vec![ids[1], ids[2], ids[3]]);
+
+    alice.send(json!({ "type": "scrollback", "around": ids[2], "limit": 1 }));
+    let message = alice.expect("scrollback")["messages"][0].clone();
+    assert_eq!(message["text"], "message 2");
+    assert_eq!(message["votes"][0]["votetype"], 1);
+
+    alice.send(json!({ "type": "scrollback", "around": 999 }));
+    assert_eq!(alice.expect_error(), MESSAGE_NOT_EXIST);
+    alice.send(json!({ "type": "scrollback", "before": 1, "after": 1 }));
+    assert_eq!(alice.expect_error(), MALFORMED);
+}
+
+#[test]
+fn search_messages() {
+    let addr = server();
+    let alice = join(addr, 1, "alice");
+
+    alice.say("the quick brown fox");
+    let fox = alice.expect("message")["id"].clone();
+    alice.say("lazy dogs sleep");
+    alice.expect("message");
+    alice.send(json!({ "type": "message", "text": "a fox reply",
+        "replyid": fox }));
+    let reply = alice.expect("message")["id"].clone();
+
+    alice.send(json!({ "type": "search", "query": "Fox" }));
+    let results = alice.expect("search")["results"].clone();
+    assert_eq!(results.as_array().unwrap().len(), 2);
+    assert_eq!(results[0]["snippet"], "a <mark>fox</mark> reply");
+    assert_eq!(results[0]["username"], "alice");
+
+    alice.send(json!({ "type": "search", "query": "fox",
+        "hasreply": false }));
+    let results = alice.expect("search")["results"].clone();
+    assert_eq!(results.as_array().unwrap().len(), 1);
+    assert_eq!(results[0]["id"], fox);
+
+    alice.send(json!({ "type": "search", "query": "fox", "hasreply": true }));
+    assert_eq!(alice.expect("search")["results"][0]["id"], reply);
+
+    // the marks are the only markup that gets through
+    alice.say("<b>fox</b> & hound");
+    alice.expect("message");
+    alice.send(json!({ "type": "search", "query": "hound" }));
+    assert_eq!(alice.expect("search")["results"][0]["snippet"],
+        "&lt;b&gt;fox&lt;/b&gt; &amp; <mark>hound</mark>");
+    // and the escaping isn't what gets searched
+    alice.send(json!({ "type": "search", "query": "amp" }));
+    assert!(alice.expect("search")["results"].as_array().unwrap().is_empty());
+
+    alice.send(json!({ "type": "search", "query": "fox", "until": 0 }));
+    assert!(alice.expect("search")["results"].as_array().unwrap().is_empty());
+
+    alice.send(json!({ "type": "search", "query": "fox", "roomid": 999 }));
+    assert_eq!(alice.expect_error(), ROOM_NOT_EXIST);
+}
+
+#[test]
+fn privileges_gate_room_changes() {
+    let addr = server();
+    let alice = join(addr, 1, "alice");
+    alice.send(json!({ "type": "editroom", "name": "renamed" }));
+    assert_eq!(alice.expect_error(), NO_PRIVILEGE);
+    alice.send(json!({ "type": "setpriv", "privtype": "readaccess",
+        "threshold": 0, "period": 0 }));
+    assert_eq!(alice.expect_error(), NO_PRIVILEGE);
+
+    let mut config = config();
+    set_limit(&mut config, PrivType::ModifyRoom, 1, 0);
+    set_limit(&mut config, PrivType::ModifyPrivs, 1, 0);
+    let addr = server_with(config);
+    let alice = join(addr, 1, "alice");
+    let bob = join(addr, 1, "bob");
+    alice.say("can anyone read this?");
+    alice.expect("message");
+
+    alice.send(json!({ "type": "editroom", "name": "renamed" }));
+    let roominfo = bob.expect("roominfo");
+    assert_eq!(roominfo["name"], "renamed");
+    assert_eq!(roominfo["desc"], "Glavra chatroom");
+
+    let authed = Client::connect(addr, "").auth("bob", "hunter2");
+    let token = authed["token"].as_str().unwrap().to_string();
+    alice.send(json!({ "type": "setpriv", "privtype": "ReadAccess",
+        "userid": authed["userid"], "threshold": 0, "period": 0 }));
+    assert_eq!(alice.expect("privilege")["privtype"], "readaccess");
+
+    let denied = Client::connect(addr, &format!("token={}&room=1", token));
+    assert_eq!(denied.expect_error(), NO_PRIVILEGE);
+    alice.send(json!({ "type": "search", "query": "read" }));
+    assert!(!alice.expect("search")["results"].as_array().unwrap().is_empty());
+    bob.send(json!({ "type": "search", "query": "read" }));
+    assert!(bob.expect("search")["results"].as_array().unwrap().is_empty());
+
+    alice.send(json!({ "type": "setpriv", "privtype": "nonsense",
+        "threshold": 0, "period": 0 }));
+    assert_eq!(alice.expect_error(), MALFORMED);
+}
+
+#[test]
+fn privilege_management() {
+    let mut config = config();
+    set_limit(&mut config, PrivType::ModifyPrivs, 1, 0);
+    let rows = config.ratelimits.len();
+    let addr = server_with(config);
+    let alice = join(addr, 1, "alice");
+    let bob = join(addr, 1, "bob");
+    let bobid = Client::connect(addr, "").auth("bob", "hunter2")["userid"]
+        .clone();
+
+    alice.send(json!({ "type": "getprivs" }));
+    let privileges = alice.expect("privileges")["privileges"].clone();
+    assert_eq!(privileges.as_array().unwrap().len(), rows);
+    assert!(privileges.as_array().unwrap().iter()
+        .all(|p| p["userid"].is_null()));
+
+    alice.send(json!({ "type": "setpriv", "privtype": "sendmessage",
+        "userid": bobid, "threshold": 50, "period": 60 }));
+    let privilege = bob.expect("privilege");
+    assert_eq!(privilege["userid"], bobid);
+    assert_eq!(privilege["threshold"], 50);
+
+    alice.send(json!({ "type": "getprivs" }));
+    let privileges = alice.expect("privileges")["privileges"].clone();
+    assert_eq!(privileges.as_array().unwrap().len(), rows + 1);
+    assert_eq!(privileges[rows]["privtype"], "sendmessage");
+    assert_eq!(privileges[rows]["period"], 60);
+
+    alice.send(json!({ "type": "clearpriv", "privtype": "sendmessage",
+        "userid": bobid }));
+    assert_eq!(bob.expect("clearpriv")["userid"], bobid);
+    alice.send(json!({ "type": "getprivs" }));
+    assert_eq!(alice.expect("privileges")["privileges"].as_array().unwrap()
+        .len(), rows);
+
+    alice.send(json!({ "type": "setpriv", "privtype": "modifyprivs",
+        "userid": bobid, "threshold": 0, "period": 0 }));
+    bob.expect("privilege");
+    bob.send(json!({ "type": "getprivs" }));
+    assert_eq!(bob.expect_error(), NO_PRIVILEGE);
+
+    // and without ReadAccess he's out of the room right away
+    alice.send(json!({ "type": "setpriv", "privtype": "readaccess",
+        "userid": bobid, "threshold": 0, "period": 0 }));
+    assert_eq!(bob.expect("leave")["roomid"], 1);
+    alice.say("still there?");
+    alice.expect("message");
+    bob.expect_none("message");
+}
+
+#[test]
+fn room_roles() {
+    let addr = server();
+    let anon = Client::connect(addr, "");
+    anon.send(json!({ "type": "room", "name": "mine", "desc": "" }));
+    assert_eq!(anon.expect_error(), NEED_LOGIN);
+
+    let lobby = Client::connect(addr, "");
+    let carolid = lobby.register("carol", "hunter2")["userid"].clone();
+    lobby.send(json!({ "type": "room", "name": "mine", "desc": "" }));
+    let room = lobby.expect("room")["id"].as_i64().unwrap() as i32;
+
+    let carol = Client::connect(addr, &format!("room={}", room));
+    carol.auth("carol", "hunter2");
+    let bob = join(addr, room, "bob");
+    let bobid = Client::connect(addr, "").auth("bob", "hunter2")["userid"]
+        .clone();
+
+    carol.send(json!({ "type": "members" }));
+    let members = carol.expect("members")["members"].clone();
+    assert_eq!(members[0]["userid"], carolid);
+    assert_eq!(members[0]["role"], "owner");
+
+    bob.send(json!({ "type": "editroom", "desc": "bob was here" }));
+    assert_eq!(bob.expect_error(), NO_PRIVILEGE);
+    bob.send(json!({ "type": "setrole", "userid": carolid,
+        "role": "banned" }));
+    assert_eq!(bob.expect_error(), NO_PRIVILEGE);
+
+    carol.send(json!({ "type": "setrole", "userid": bobid,
+        "role": "moderator" }));
+    assert_eq!(bob.expect("role")["role"], "moderator");
+    bob.send(json!({ "type": "editroom", "desc": "bob was here" }));
+    assert_eq!(carol.expect("roominfo")["desc"], "bob was here");
+    // moderators can't get at the owner through privileges either
+    bob.send(json!({ "type": "setpriv", "privtype": "readaccess",
+        "userid": carolid, "threshold": 0, "period": 0 }));
+    assert_eq!(bob.expect_error(), NO_PRIVILEGE);
+    bob.send(json!({ "type": "clearpriv", "privtype": "readaccess",
+        "userid": carolid }));
+    assert_eq!(bob.expect_error(), NO_PRIVILEGE);
+
+    carol.send(json!({ "type": "setrole", "userid": bobid,
+        "role": "banned" }));
+    assert_eq!(bob.expect("role")["role"], "banned");
+    assert_eq!(bob.expect("leave")["roomid"], room);
+    carol.say("bob's gone");
+    carol.expect("message");
+    bob.expect_none("message");
+    bob.send(json!({ "type": "message", "text": "let me in",
+        "roomid": room }));
+    assert_eq!(bob.expect_error(), NOT_JOINED);
+    let token = Client::connect(addr, "").auth("bob", "hunter2")["token"]
+        .as_str().unwrap().to_string();
+    let rejoin = Client::connect(addr,
+        &format!("token={}&room={}", token, room));
+    assert_eq!(rejoin.expect_error(), NO_PRIVILEGE);
+}
+
+#[test]
+fn room_settings_and_archival() {
+    let addr = server();
+    let lobby = Client::connect(addr, "");
+    lobby.register("carol", "hunter2");
+    lobby.send(json!({ "type": "room", "name": "mine", "desc": "" }));
+    let room = lobby.expect("room")["id"].as_i64().unwrap() as i32;
+    let carol = Client::connect(addr, &format!("room={}", room));
+    carol.auth("carol", "hunter2");
+    let bob = join(addr, room, "bob");
+
+    carol.send(json!({ "type": "editroom", "name": "ours",
+        "topic": "nothing in particular" }));
+    let roominfo = bob.expect("roominfo");
+    assert_eq!(roominfo["name"], "ours");
+    assert_eq!(roominfo["topic"], "nothing in particular");
+    assert_eq!(roominfo["archived"], false);
+    bob.say("last words");
+    bob.expect("message");
+
+    bob.send(json!({ "type": "archiveroom" }));
+    assert_eq!(bob.expect_error(), NO_PRIVILEGE);
+    carol.send(json!({ "type": "archiveroom" }));
+    assert_eq!(bob.expect("roominfo")["archived"], true);
+
+    bob.say("anyone here?");
+    assert_eq!(bob.expect_error(), ROOM_ARCHIVED);
+    carol.send(json!({ "type": "editroom", "topic": "closed" }));
+    assert_eq!(carol.expect_error(), ROOM_ARCHIVED);
+
+    let listing = Client::connect(addr, "queryrooms");
+    let listed = listing.expect("roomlist");
+    assert_eq!(listed["id"], room);
+    assert_eq!(listed["archived"], true);
+
+    // archived rooms can still be read
+    let reader = Client::connect(addr, &format!("room={}", room));
+    assert_eq!(reader.expect("roominfo")["archived"], true);
+    assert_eq!(reader.expect("message")["text"], "last words");
+
+    carol.send(json!({ "type": "unarchiveroom" }));
+    assert_eq!(bob.expect("roominfo")["archived"], false);
+    bob.say("back again");
+    assert_eq!(bob.expect("message")["text"], "back again");
+}
+
+#[test]
+fn invite_only_rooms() {
+    let addr = server();
+    let lobby = Client::connect(addr, "");
+    lobby.register("carol", "hunter2");
+    lobby.send(json!({ "type": "room", "name": "secret", "desc": "" }));
+    let room = lobby.expect("room")["id"].as_i64().unwrap() as i32;
+    let carol = Client::connect(addr, &format!("room={}", room));
+    carol.auth("carol", "hunter2");
+
+    carol.send(json!({ "type": "setvisibility", "visibility": "invite" }));
+    assert_eq!(carol.expect("roominfo")["visibility"], "invite");
+    carol.send(json!({ "type": "createinvite" }));
+    let invite = carol.expect("invite");
+    assert_eq!(invite["roomid"], room);
+    let invite = invite["token"].as_str().unwrap().to_string();
+
+    // hidden from the listing and closed to outsiders
+    let listing = Client::connect(addr, "queryrooms");
+    assert_eq!(listing.expect("roomlist")["id"], 1);
+    assert_eq!(Client::connect(addr, &format!("room={}", room))
+        .expect_error(), NOT_MEMBER);
+
+    // nor can they read its messages by id from elsewhere
+    carol.say("members only");
+    let secret = carol.expect("message")["id"].clone();
+    let outsider = Client::connect(addr, "");
+    outsider.send(json!({ "type": "history", "id": secret }));
+    assert_eq!(outsider.expect_error(), NOT_MEMBER);
+    outsider.send(json!({ "type": "thread", "id": secret }));
+    assert_eq!(outsider.expect_error(), NOT_MEMBER);
+
+    let token = Client::connect(addr, "").register("dave", "hunter2")["token"]
+        .as_str().unwrap().to_string();
+    assert_eq!(Client::connect(addr, &format!("token={}&room={}", token, room))
+        .expect_error(), NOT_MEMBER);
+    assert_eq!(Client::connect(addr, &format!("token={}&room={}&invite=nope",
+        token, room)).expect_error(), NOT_MEMBER);
+
+    let dave = Client::connect(addr,
+        &format!("token={}&room={}&invite={}", token, room, invite));
+    assert_eq!(dave.expect("roominfo")["visibility"], "invite");
+
+    // membership sticks, and members see the room listed
+    let dave = Client::connect(addr, &format!("token={}&room={}", token, room));
+    dave.expect("roominfo");
+    let listing = Client::connect(addr,
+        &format!("token={}&queryrooms", token));
+    assert_eq!(listing.expect("roomlist")["id"], room);
+}
+
+#[test]
+fn direct_messages() {
+    let addr = server();
+    let alice = Client::connect(addr, "");
+    let aliceid = alice.register("alice", "hunter2")["userid"].clone();
+    let bob = Client::connect(addr, "");
+    let bobid = bob.register("bob", "hunter2")["userid"].clone();
+    let eve = Client::connect(addr, "");
+    let evetoken = eve.register("eve", "hunter2")["token"].as_str().unwrap()
+        .to_string();
+
+    alice.send(json!({ "type": "dm", "userids": [bobid] }));
+    let dm = alice.expect("dm");
+    let room = dm["roomid"].as_i64().unwrap() as i32;
+    assert_eq!(dm["users"][0]["id"], aliceid);
+    assert_eq!(dm["users"][1]["username"], "bob");
+
+    // the same pair always gets the same conversation
+    bob.send(json!({ "type": "dm", "userids": [aliceid] }));
+    assert_eq!(bob.expect("dm")["roomid"], room);
+    bob.send(json!({ "type": "dms" }));
+    assert_eq!(bob.expect("dmlist")["dms"][0]["roomid"], room);
+    eve.send(json!({ "type": "dms" }));
+    assert!(eve.expect("dmlist")["dms"].as_array().unwrap().is_empty());
+
+    alice.send(json!({ "type": "dm", "userids": [] }));
+    assert_eq!(alice.expect_error(), MALFORMED);
+    alice.send(json!({ "type": "dm", "userids": [999] }));
+    assert_eq!(alice.expect_error(), USER_NOT_EXIST);
+
+    let bobtoken = bob.auth("bob", "hunter2")["token"].as_str().unwrap()
+        .to_string();
+    let conversation = Client::connect(addr,
+        &format!("token={}&room={}", bobtoken, room));
+    assert_eq!(conversation.expect("roominfo")["direct"], true);
+    conversation.say("psst");
+    let psst = conversation.expect("message");
+    assert_eq!(psst["text"], "psst");
+
+    // a third user can't read the conversation by message id either
+    let eve = Client::connect(addr, &format!("token={}", evetoken));
+    eve.expect("auth");
+    eve.send(json!({ "type": "history", "id": psst["id"] }));
+    assert_eq!(eve.expect_error(), NOT_MEMBER);
+    eve.send(json!({ "type": "thread", "id": psst["id"] }));
+    assert_eq!(eve.expect_error(), NOT_MEMBER);
+
+    assert_eq!(Client::connect(addr,
+        &format!("token={}&room={}", evetoken, room)).expect_error(),
+        NOT_MEMBER);
+    let listing = Client::connect(addr, &format!("token={}&queryrooms",
+        bobtoken));
+    assert_eq!(listing.expect("roomlist")["id"], 1);
+}
+
+#[test]
+fn move_messages() {
+    let mut config = config();
+    set_limit(&mut config, PrivType::MoveOut, 1, 0);
+    let addr = server_with(config);
+    let lobby = Client::connect(addr, "");
+    lobby.register("carol", "hunter2");
+    lobby.send(json!({ "type": "room", "name": "offtopic", "desc": "" }));
+    let target = lobby.expect("room")["id"].as_i64().unwrap() as i32;
+
+    let bob = join(addr, 1, "bob");
+    bob.say("first");
+    let first = bob.expect("message")["id"].clone();
+    bob.say("second");
+    let second = bob.expect("message")["id"].clone();
+    bob.say("staying");
+    let staying = bob.expect("message")["id"].clone();
+    let there = join(addr, target, "dave");
+
+    // everyone may move messages out of room 1, but only carol, who owns
+    // the other room, may move them in there
+    bob.send(json!({ "type": "move", "ids": [first], "to": target }));
+    assert_eq!(bob.expect_error(), NO_PRIVILEGE);
+
+    let carol = Client::connect(addr, "room=1");
+    carol.auth("carol", "hunter2");
+    carol.send(json!({ "type": "move", "ids": [first, 999],
+        "to": target }));
+    assert_eq!(carol.expect_error(), MESSAGE_NOT_EXIST);
+
+    carol.send(json!({ "type": "move", "ids": [second, first],
+        "to": target }));
+    let moveout = bob.expect("moveout");
+    assert_eq!(moveout["ids"], json!([first, second]));
+    assert_eq!(moveout["roomid"], 1);
+    assert_eq!(moveout["to"], target);
+    assert_eq!(bob.expect("message")["text"], "2 messages moved to offtopic");
+
+    let movein = there.expect("movein");
+    assert_eq!(movein["roomid"], target);
+    assert_eq!(movein["from"], 1);
+    assert_eq!(movein["messages"][0]["text"], "first");
+    assert_eq!(movein["messages"][1]["id"], second);
+
+    bob.send(json!({ "type": "scrollback", "limit": 3 }));
+    let ids: Vec<_> = bob.expect("scrollback")["messages"].as_array()
+        .unwrap().iter().map(|m| m["id"].clone()).collect();
+    assert!(!ids.contains(&first) && ids.contains(&staying));
+}
+
+#[test]
+fn threads() {
+    let addr = server();
+    let alice = join(addr, 1, "alice");
+
+    alice.say("root");
+    let root = alice.expect("message")["id"].clone();
+    alice.send(json!({ "type": "message", "text": "reply", "replyid": root }));
+    let reply = alice.expect("message")["id"].clone();
+    alice.send(json!({ "type": "message", "text": "nested",
+        "replyid": reply }));
+    let nested = alice.expect("message")["id"].clone();
+    alice.send(json!({ "type": "message", "text": "another",
+        "replyid": reply }));
+    let another = alice.expect("message")["id"].clone();
+
+    alice.send(json!({ "type": "thread", "id": reply }));
+    let thread = alice.expect("thread");
+    assert_eq!(thread["roomid"], 1);
+    assert_eq!(thread["ancestors"][0]["id"], root);
+    assert_eq!(thread["ancestors"][0]["replycount"], 1);
+    assert_eq!(thread["message"]["replycount"], 2);
+    assert_eq!(thread["replies"][0]["id"], nested);
+    assert_eq!(thread["replies"][1]["id"], another);
+
+    alice.send(json!({ "type": "message", "text": "nope", "replyid": 999 }));
+    assert_eq!(alice.expect_error(), INVALID_REPLY);
+    // replies can't point forwards, which would allow cycles
+    alice.send(json!({ "type": "edit", "id": reply, "text": "reply",
+        "replyid": nested }));
+    assert_eq!(alice.expect_error(), INVALID_REPLY);
+
+    let lobby = Client::connect(addr, "");
+    lobby.register("carol", "hunter2");
+    lobby.send(json!({ "type": "room", "name": "other", "desc": "" }));
+    let other = lobby.expect("room")["id"].as_i64().unwrap() as i32;
+    let dave = join(addr, other, "dave");
+    dave.send(json!({ "type": "message", "text": "elsewhere",
+        "replyid": root }));
+    assert_eq!(dave.expect_error(), INVALID_REPLY);
+}
+
+#[test]
+fn moved_replies_leave_the_thread() {
+    let mut config = config();
+    set_limit(&mut config, PrivType::MoveOut, 1, 0);
+    let addr = server_with(config);
+    let lobby = Client::connect(addr, "");
+    lobby.register("carol", "hunter2");
+    lobby.send(json!({ "type": "room", "name": "secret", "desc": "" }));
+    let secret = lobby.expect("room")["id"].as_i64().unwrap() as i32;
+    let owner = Client::connect(addr, &format!("room={}", secret));
+    owner.auth("carol", "hunter2");
+    owner.send(json!({ "type": "setvisibility", "visibility": "invite" }));
+    owner.expect("roominfo");
+
+    let bob = join(addr, 1, "bob");
+    bob.say("root");
+    let root = bob.expect("message")["id"].clone();
+    bob.send(json!({ "type": "message", "text": "psst", "replyid": root }));
+    let reply = bob.expect("message")["id"].clone();
+
+    let carol = Client::connect(addr, "room=1");
+    carol.auth("carol", "hunter2");
+    carol.send(json!({ "type": "move", "ids": [reply], "to": secret }));
+    bob.expect("moveout");
+
+    // bob can't see into the room the reply went to, so it's gone from here
+    bob.send(json!({ "type": "thread", "id": root }));
+    let thread = bob.expect("thread");
+    assert_eq!(thread["message"]["replycount"], 0);
+    assert!(thread["replies"].as_array().unwrap().is_empty());
+}
+
+#[test]
+fn mentions_and_inbox() {
+    let addr = server();
+    let alice = join(addr, 1, "alice");
+    alice.say("hello");
+    let hello = alice.expect("message")["id"].clone();
+
+    // bob is logged in, but not in any room
+    let bob = Client::connect(addr, "");
+    bob.register("bob", "hunter2");
+
+    alice.say("hey @bob, and @nobody, and mail@bob.example");
+    let mention = bob.expect("notify");
+    assert_eq!(mention["kind"], "mention");
+    assert_eq!(mention["roomid"], 1);
+    assert_eq!(mention["message"]["username"], "alice");
+    bob.expect_none("notify");
+
+    let carol = join(addr, 1, "carol");
+    carol.send(json!({ "type": "message", "text": "hi @alice",
+        "replyid": hello }));
+    carol.expect("message");
+    // replying and mentioning both notify, as different kinds
+    let kinds: Vec<_> = (0..2).map(|_| alice.expect("notify")["kind"].clone())
+        .collect();
+    assert!(kinds.contains(&json!("reply")));
+    assert!(kinds.contains(&json!("mention")));
+
+    bob.send(json!({ "type": "inbox" }));
+    let inbox = bob.expect("inbox")["notifications"].clone();
+    assert_eq!(inbox.as_array().unwrap().len(), 1);
+    let id = inbox[0]["id"].clone();
+    bob.send(json!({ "type": "readinbox", "ids": [id] }));
+    bob.send(json!({ "type": "inbox" }));
+    assert!(bob.expect("inbox")["notifications"].as_array().unwrap()
+        .is_empty());
+
+    alice.send(json!({ "type": "readinbox" }));
+    alice.send(json!({ "type": "inbox" }));
+    assert!(alice.expect("inbox")["notifications"].as_array().unwrap()
+        .is_empty());
+}
+
+#[test]
+fn read_markers() {
+    let addr = server();
+    let bob = join(addr, 1, "bob");
+    let token = Client::connect(addr, "").register("alice", "hunter2")
+        ["token"].as_str().unwrap().to_string();
+    let alice = Client::connect(addr, &format!("token={}&room=1", token));
+    alice.expect("roominfo");
+
+    let ids: Vec<_> = ["one", "two", "three"].iter().map(|text| {
+        bob.say(text);
+        bob.expect("message")["id"].clone()
+    }).collect();
+
+    alice.send(json!({ "type": "markread", "messageid": ids[1] }));
+    assert_eq!(alice.expect("readmarker")["messageid"], ids[1]);
+    // markers never go backwards
+    alice.send(json!({ "type": "markread", "messageid": ids[0] }));
+    assert_eq!(alice.expect("readmarker")["messageid"], ids[1]);
+
+    let listing = Client::connect(addr, &format!("token={}&queryrooms",
+        token));
+    assert_eq!(listing.expect("roomlist")["unread"], 1);
+    let anon = Client::connect(addr, "queryrooms");
+    assert!(anon.expect("roomlist").get("unread").is_none());
+
+    let again = Client::connect(addr, &format!("token={}&room=1", token));
+    let marker = again.expect("readmarker");
+    assert_eq!(marker["messageid"], ids[1]);
+    assert_eq!(again.recv()["id"], ids[2]);
+
+    alice.send(json!({ "type": "markread" }));
+    alice.expect("readmarker");
+    let listing = Client::connect(addr, &format!("token={}&queryrooms",
+        token));
+    assert_eq!(listing.expect("roomlist")["unread"], 0);
+}
+
+#[test]
+fn presence_and_who() {
+    let addr = server();
+    let alice = join(addr, 1, "alice");
+    let token = Client::connect(addr, "").register("bob", "hunter2")
+        ["token"].as_str().unwrap().to_string();
+
+    let bob = Client::connect(addr, &format!("token={}&room=1", token));
+    let arrived = alice.expect("presence");
+    assert_eq!(arrived["username"], "bob");
+    assert_eq!(arrived["online"], true);
+
+    // a second tab doesn't count as arriving again
+    let tab = Client::connect(addr, &format!("token={}&room=1", token));
+    tab.expect("roominfo");
+    alice.expect_none("presence");
+
+    alice.send(json!({ "type": "who" }));
+    let who = alice.expect("who");
+    assert_eq!(who["roomid"], 1);
+    let names: Vec<_> = who["users"].as_array().unwrap().iter()
+        .map(|user| user["username"].clone()).collect();
+    assert_eq!(names, vec![json!("alice"), json!("bob")]);
+
+    tab.close();
+    alice.expect_none("presence");
+    bob.close();
+    let departed = alice.expect("presence");
+    assert_eq!(departed["username"], "bob");
+    assert_eq!(departed["online"], false);
+
+    // presence never shows up in the message log
+    let late = Client::connect(addr, "room=1");
+    late.expect("roominfo");
+    late.expect_none("message");
+}
+
+#[test]
+fn rooms_on_one_connection() {
+    let addr = server();
+    let lobby = Client::connect(addr, "");
+    lobby.register("carol", "hunter2");
+    lobby.send(json!({ "type": "room", "name": "other", "desc": "" }));
+    let other = lobby.expect("room")["id"].as_i64().unwrap() as i32;
+
+    let alice = join(addr, 1, "alice");
+    alice.send(json!({ "type": "join", "roomid": other }));
+    assert_eq!(alice.expect("roominfo")["roomid"], other);
+    alice.send(json!({ "type": "join", "roomid": 999 }));
+    assert_eq!(alice.expect_error(), ROOM_NOT_EXIST);
+
+    // requests go to the room they name, or else the one from the URL
+    alice.send(json!({ "type": "message", "text": "over here",
+        "roomid": other }));
+    let message = alice.expect("message");
+    assert_eq!(message["roomid"], other);
+    assert_eq!(message["text"], "over here");
+    alice.say("back home");
+    assert_eq!(alice.expect("message")["roomid"], 1);
+
+    let bob = join(addr, other, "bob");
+    bob.say("hi alice");
+    assert_eq!(alice.expect("message")["text"], "hi alice");
+    alice.send(json!({ "type": "who", "roomid": other }));
+    assert_eq!(alice.expect("who")["users"].as_array().unwrap().len(), 2);
+
+    alice.send(json!({ "type": "leave", "roomid": other }));
+    assert_eq!(alice.expect("leave")["roomid"], other);
+    assert_eq!(bob.expect("presence")["online"], false);
+    bob.say("hello?");
+    alice.expect_none("message");
+    alice.send(json!({ "type": "message", "text": "hm",
+        "roomid": other }));
+    assert_eq!(alice.expect_error(), NOT_JOINED);
+    alice.send(json!({ "type": "leave", "roomid": other }));
+    assert_eq!(alice.expect_error(), NOT_JOINED);
+
+    alice.send(json!({ "type": "leave", "roomid": 1 }));
+    alice.expect("leave");
+    alice.say("anyone?");
+    assert_eq!(alice.expect_error(), NO_ROOM_ID);
+}
+
+#[test]
+fn sessions_and_logout() {
+    let addr = server();
+    let first = Client::connect(addr, "").register("alice", "hunter2")
+        ["token"].as_str().unwrap().to_string();
+    let laptop = Client::connect(addr, "");
+    laptop.send(json!({ "type": "auth", "username": "alice",
+        "password": "hunter2", "label": "laptop" }));
+    let second = laptop.expect("auth")["token"].as_str().unwrap().to_string();
+    assert!(first != second);
+
+    let phone = Client::connect(addr, &format!("token={}", first));
+    assert_eq!(phone.expect("auth")["username"], "alice");
+    phone.send(json!({ "type": "sessions" }));
+    let sessions = phone.expect("sessions")["sessions"].clone();
+    assert_eq!(sessions.as_array().unwrap().len(), 2);
+    assert_eq!(sessions[0]["label"], "laptop");
+    assert_eq!(sessions[0]["current"], false);
+    assert_eq!(sessions[1]["current"], true);
+
+    // ending a session logs out everything using it, and its token with it
+    phone.send(json!({ "type": "logout", "id": sessions[0]["id"] }));
+    assert_eq!(laptop.expect("logout")["id"], sessions[0]["id"]);
+    assert_eq!(Client::connect(addr, &format!("token={}", second))
+        .expect_error(), BAD_TOKEN);
+    phone.send(json!({ "type": "logout", "id": sessions[0]["id"] }));
+    assert_eq!(phone.expect_error(), SESSION_NOT_EXIST);
+
+    let again = Client::connect(addr, "").auth("alice", "hunter2")["token"]
+        .as_str().unwrap().to_string();
+    phone.send(json!({ "type": "logoutall" }));
+    phone.expect("logout");
+    for token in &[first, again] {
+        assert_eq!(Client::connect(addr, &format!("token={}", token))
+            .expect_error(), BAD_TOKEN);
+    }
+    assert_eq!(Client::connect(addr, "token=nonsense").expect_error(),
+        BAD_TOKEN);
+
+    let anon = Client::connect(addr, "");
+    anon.send(json!({ "type": "logout" }));
+    assert_eq!(anon.expect_error(), NEED_LOGIN);
+}
+
+#[test]
+fn sessions_expire() {
+    let mut config = config();
+    config.session_lifetime = 0;
+    let addr = server_with(config);
+    let token = Client::connect(addr, "").register("alice", "hunter2")
+        ["token"].as_str().unwrap().to_string();
+    assert_eq!(Client::connect(addr, &format!("token={}", token))
+        .expect_error(), TOKEN_EXPIRED);
+}
+
+#[test]
+fn change_password() {
+    let addr = server();
+    let alice = Client::connect(addr, "");
+    alice.register("alice", "hunter2");
+    let laptop = Client::connect(addr, "");
+    assert_eq!(laptop.auth("alice", "hunter2")["success"], true);
+
+    alice.send(json!({ "type": "changepassword", "password": "wrong",
+        "newpassword": "correct horse" }));
+    assert_eq!(alice.expect("changepassword")["success"], false);
+    alice.send(json!({ "type": "changepassword", "password": "hunter2",
+        "newpassword": "correct horse" }));
+    assert_eq!(alice.expect("changepassword")["success"], true);
+
+    // every other session is logged out, but this one stays
+    laptop.expect("logout");
+    alice.send(json!({ "type": "sessions" }));
+    let sessions = alice.expect("sessions")["sessions"].clone();
+    assert_eq!(sessions.as_array().unwrap().len(), 1);
+    assert_eq!(sessions[0]["current"], true);
+
+    assert_eq!(Client::connect(addr, "").auth("alice", "hunter2")["success"],
+        false);
+    assert_eq!(Client::connect(addr, "")
+        .auth("alice", "correct horse")["success"], true);
+
+    let anon = Client::connect(addr, "");
+    anon.send(json!({ "type": "changepassword", "password": "hunter2",
+        "newpassword": "hunter3" }));
+    assert_eq!(anon.expect_error(), NEED_LOGIN);
+}
+
+#[test]
+fn reset_password() {
+    let (addr, glavra) = shared_server();
+    let alice = Client::connect(addr, "");
+    alice.register("alice", "hunter2");
+    assert!(glavra.reset_code("nobody").is_none());
+    let stale = glavra.reset_code("alice").unwrap();
+    let code = glavra.reset_code("alice").unwrap();
+
+    // making a new code throws away the old one
+    let anon = Client::connect(addr, "");
+    anon.send(json!({ "type": "resetpassword", "code": stale,
+        "password": "correct horse" }));
+    assert_eq!(anon.expect("resetpassword")["success"], false);
+
+    anon.send(json!({ "type": "resetpassword", "code": code,
+        "password": "correct horse" }));
+    assert_eq!(anon.expect("resetpassword")["success"], true);
+    alice.expect("logout");
+    assert_eq!(Client::connect(addr, "").auth("alice", "hunter2")["success"],
+        false);
+    assert_eq!(Client::connect(addr, "")
+        .auth("alice", "correct horse")["success"], true);
+
+    // and each code only works once
+    anon.send(json!({ "type": "resetpassword", "code": code,
+        "password": "hunter3" }));
+    assert_eq!(anon.expect("resetpassword")["success"], false);
+}
+
+#[test]
+fn failed_logins_back_off() {
+    let mut config = config();
+    config.login_attempts = 2;
+    let addr = server_with(config);
+    Client::connect(addr, "").register("alice", "hunter2");
+
+    let client = Client::connect(addr, "");
+    for _ in 0..3 {
+        assert_eq!(client.auth("alice", "wrong")["success"], false);
+    }
+
+    // now even the right password has to wait, as does anyone else trying
+    // from the same address
+    for username in &["alice", "bob"] {
+        client.send(json!({ "type": "auth", "username": username,
+            "password": "hunter2" }));
+        let error = client.expect("error");
+        assert_eq!(error["code"], LOGIN_THROTTLED);
+        assert!(error["retryafter"].as_i64().unwrap() >= 1);
+    }
+
+    thread::sleep(Duration::from_millis(1100));
+    assert_eq!(client.auth("alice", "hunter2")["success"], true);
+}
+
+#[test]
+fn registrations_are_limited() {
+    let mut config = config();
+    config.register_limit = 2;
+    let addr = server_with(config);
+    assert_eq!(Client::connect(addr, "").register("alice", "hunter2")
+        ["success"], true);
+    // attempts count whether or not they succeed
+    assert_eq!(Client::connect(addr, "").register("alice", "hunter2")
+        ["success"], false);
+
+    let client = Client::connect(addr, "");
+    client.send(json!({ "type": "register", "username": "bob",
+        "password": "hunter2" }));
+    let error = client.expect("error");
+    assert_eq!(error["code"], REGISTER_THROTTLED);
+    assert!(error["retryafter"].as_i64().unwrap() > 3000);
+}
+
+#[test]
+fn passwords_stay_out_of_debug_output() {
+    let request: Request = serde_json::from_value(json!({
+        "type": "changepassword", "password": "hunter2",
+        "newpassword": "correct horse" })).unwrap();
+    let debug = format!("{:?}", request);
+    assert!(!debug.contains("hunter2") && !debug.contains("correct horse"));
+}
+
+#[test]
+fn legacy_passwords_upgrade_on_login() {
+    // the empty password, hashed with bcrypt the way users' passwords were
+    // before argon2id (see tests/password.rs)
+    let legacy =
+        "$bcrypt$c=10$m3ydKtoP0HCRyRXRUXcB1g$ey4DEGpDyXU4Idtoi1zHWQsY/fm6VEYy";
+    let (addr, glavra) = shared_server();
+    glavra.store().create_user("old", legacy).unwrap();
+
+    let client = Client::connect(addr, "");
+    assert_eq!(client.auth("old", "wrong")["success"], false);
+    assert_eq!(glavra.store().find_user("old").unwrap().password, legacy);
+
+    assert_eq!(client.auth("old", "")["success"], true);
+    let upgraded = glavra.store().find_user("old").unwrap().password;
+    assert!(upgraded.starts_with("$argon2id$"));
+    assert_eq!(client.auth("old", "")["success"], true);
+    assert_eq!(glavra.store().find_user("old").unwrap().password, upgraded);
+}
+
+#[test]
+fn failed_hashes_are_reported() {
+    // argon2 won't work with less than 8 KiB per lane
+    let mut config = config();
+    config.argon2_memory = 1;
+    let addr = server_with(config);
+    let client = Client::connect(addr, "");
+    client.send(json!({ "type": "register", "username": "alice",
+        "password": "hunter2" }));
+    assert_eq!(client.expect_error(), HASH_FAILED);
+}